@@ -0,0 +1,47 @@
+// Rewards
+pub const MIN_APY: u64 = 0;
+pub const MAX_APY: u64 = 1000; // 1000% max APY
+pub const SECONDS_PER_DAY: u128 = 24 * 60 * 60;
+pub const SECONDS_PER_YEAR: u128 = 365 * SECONDS_PER_DAY;
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000; // Fixed-point scale for the reward index
+pub const MIN_EMISSION_STEP_INTERVAL: i64 = 24 * 60 * 60; // 1 day
+pub const EMISSION_DECAY_PRECISION: u128 = 1_000_000_000_000; // Fixed-point scale for step decay factors
+pub const EXCHANGE_RATE_PRECISION: u128 = 1_000_000_000; // Underlying per receipt scale
+pub const LOYALTY_BONUS_PERIOD: i64 = 30 * 24 * 60 * 60; // 30 days
+pub const LOYALTY_BONUS_PERCENT: u128 = 10; // 10%
+
+// Positions
+pub const MAX_LOCK_DURATION: i64 = 4 * 365 * 24 * 60 * 60; // 4 years
+pub const BPS_DENOMINATOR: u128 = 10_000;
+pub const MAX_LOCK_MULTIPLIER_BPS: u64 = 30_000; // 3x
+pub const MAX_LOCK_TIERS: usize = 5;
+pub const DEFAULT_LOCK_TIERS: [(i64, u64); MAX_LOCK_TIERS] = [
+    (0, 10_000),                       // No lock, 1x
+    (30 * 24 * 60 * 60, 11_000),       // 30 days, 1.1x
+    (90 * 24 * 60 * 60, 12_500),       // 90 days, 1.25x
+    (180 * 24 * 60 * 60, 15_000),      // 180 days, 1.5x
+    (365 * 24 * 60 * 60, 20_000),      // 365 days, 2x
+];
+
+// Delegated staking
+pub const MAX_APPROVED_DEPOSITORS: usize = 4;
+
+// Keepers
+pub const MAX_KEEPER_TIP_BPS: u64 = 100; // 1% of compounded rewards
+pub const MAX_COMPOUND_BATCH: usize = 10; // Positions per crank
+
+// Treasury multisig
+pub const MAX_TREASURY_SIGNERS: usize = 10;
+pub const MAX_PROTOCOL_FEE_BPS: u64 = 2_000; // 20% of each reward payout
+
+// Withdrawal queue
+pub const MAX_BATCH_SIZE: usize = 10; // Requests paid per process call
+
+// Bonds
+pub const BOND_PRICE_PRECISION: u128 = 1_000_000_000; // Quote units per payout unit scale
+pub const DEBT_RATIO_PRECISION: u128 = 1_000_000_000;
+pub const MIN_BOND_VESTING_TERM: i64 = 24 * 60 * 60; // 1 day
+pub const MAX_BOND_ADJUSTMENT_BPS: u64 = 250; // 2.5% of the control variable per step
+
+// Emergency withdrawals
+pub const MAX_EMERGENCY_PENALTY: u64 = 50; // 50%
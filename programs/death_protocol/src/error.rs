@@ -30,4 +30,120 @@ pub enum ProtocolError {
     InvalidProposalType,
     #[msg("No rewards available to claim")]
     NoRewardsAvailable,
-}
\ No newline at end of file
+    #[msg("No tokens are unbonding")]
+    NothingUnbonding,
+    #[msg("Unbonding period still active")]
+    UnbondingPeriodActive,
+    #[msg("Emergency withdrawal cooldown active")]
+    EmergencyWithdrawCooldown,
+    #[msg("Insufficient balance")]
+    InsufficientBalance,
+    #[msg("Invalid emergency penalty")]
+    InvalidPenalty,
+    #[msg("No stakers to distribute to")]
+    NoStakers,
+    #[msg("Invalid treasury account")]
+    InvalidTreasuryAccount,
+    #[msg("Invalid authority")]
+    InvalidAuthority,
+    #[msg("APY out of bounds")]
+    InvalidAPY,
+    #[msg("Minimum stake exceeds maximum stake")]
+    InvalidStakeRange,
+    #[msg("Invalid cooldown period")]
+    InvalidCooldown,
+    #[msg("Protocol is not paused")]
+    ProtocolNotPaused,
+    #[msg("Reward reserve is empty")]
+    RewardReserveEmpty,
+    #[msg("Invalid funding amount")]
+    InvalidFundingAmount,
+    #[msg("Invalid emission schedule")]
+    InvalidEmissionSchedule,
+    #[msg("Staking pool does not match")]
+    InvalidPool,
+    #[msg("Liquid staking is not enabled for this pool")]
+    LiquidStakingDisabled,
+    #[msg("Liquid staking is already enabled for this pool")]
+    LiquidStakingEnabled,
+    #[msg("Invalid receipt amount")]
+    InvalidReceiptAmount,
+    #[msg("Invalid lock duration")]
+    InvalidLockDuration,
+    #[msg("Position is still locked")]
+    PositionLocked,
+    #[msg("Position still holds stake or rewards")]
+    PositionNotEmpty,
+    #[msg("Invalid split amount")]
+    InvalidSplitAmount,
+    #[msg("Cannot merge a position into itself")]
+    SamePosition,
+    #[msg("Invalid lock tier")]
+    InvalidLockTier,
+    #[msg("Positions have different lock tiers")]
+    IncompatiblePositions,
+    #[msg("Keeper tip exceeds maximum")]
+    InvalidKeeperTip,
+    #[msg("Invalid compound batch")]
+    InvalidCompoundBatch,
+    #[msg("Too many approved depositors")]
+    TooManyDepositors,
+    #[msg("Calling program is not an approved depositor")]
+    UnapprovedDepositor,
+    #[msg("Withdrawal request was already cancelled")]
+    WithdrawalCancelled,
+    #[msg("Invalid withdrawal request")]
+    InvalidWithdrawalRequest,
+    #[msg("No withdrawal requests are due")]
+    NothingToProcess,
+    #[msg("Invalid liquidity buffer")]
+    InvalidLiquidityBuffer,
+    #[msg("Invalid outflow epoch")]
+    InvalidOutflowEpoch,
+    #[msg("Epoch outflow cap reached")]
+    OutflowCapReached,
+    #[msg("Crank already ran within the keeper interval")]
+    CrankTooSoon,
+    #[msg("Invalid keeper config")]
+    InvalidKeeperConfig,
+    #[msg("Reward reserve is above the top-up threshold")]
+    RewardReserveHealthy,
+    #[msg("Invalid bond market config")]
+    InvalidBondConfig,
+    #[msg("Invalid bond amount")]
+    InvalidBondAmount,
+    #[msg("Bond market capacity exceeded")]
+    BondCapacityExceeded,
+    #[msg("Bond price exceeds the maximum accepted")]
+    BondPriceExceeded,
+    #[msg("Nothing has vested since the last redemption")]
+    NothingToRedeem,
+    #[msg("Invalid price feed")]
+    InvalidPriceFeed,
+    #[msg("Price feed is stale")]
+    StalePrice,
+    #[msg("Price confidence interval too wide")]
+    PriceConfidenceTooLow,
+    #[msg("Invalid treasury signer set")]
+    InvalidTreasurySigners,
+    #[msg("Signer is not a treasury owner")]
+    NotTreasurySigner,
+    #[msg("Transaction already approved by this signer")]
+    AlreadyApproved,
+    #[msg("Not enough approvals to execute")]
+    InsufficientSignatures,
+    #[msg("Transaction already executed")]
+    TransactionAlreadyExecuted,
+    #[msg("Treasury signer set changed since the transaction was proposed")]
+    StaleTransaction,
+    #[msg("Withdrawal limit exceeded")]
+    WithdrawalLimitExceeded,
+    #[msg("Invalid withdrawal period")]
+    InvalidWithdrawalPeriod,
+    #[msg("Protocol fee out of bounds")]
+    InvalidProtocolFee,
+    #[msg("Previous unbonding has not been completed")]
+    UnbondingInProgress,
+    #[msg("Calling program could not be identified")]
+    UnknownCallingProgram,
+}
@@ -0,0 +1,342 @@
+use anchor_lang::prelude::*;
+use crate::state::{ExitRoute, KeeperTask, RewardMode, TreasuryAction};
+
+#[event]
+pub struct PoolCreatedEvent {
+    pub pool: Pubkey,
+    pub stake_mint: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConfigUpdateEvent {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub current_apy: u64,
+    pub min_stake: u64,
+    pub max_stake: u64,
+    pub emergency_cooldown: i64,
+    pub emergency_penalty: u64,
+    pub unbonding_period: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolPausedEvent {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolUnpausedEvent {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardsFundedEvent {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub reward_reserve: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StakeForEvent {
+    pub pool: Pubkey,
+    pub payer: Pubkey,
+    pub beneficiary: Pubkey,
+    pub caller_program: Option<Pubkey>,
+    pub position: u64,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositorAllowlistUpdatedEvent {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub programs: Vec<Pubkey>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardsClaimedEvent {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub position: u64,
+    pub gross: u64,
+    pub fee: u64,
+    pub net: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidRewardsCompoundedEvent {
+    pub pool: Pubkey,
+    pub gross: u64,
+    pub fee: u64,
+    pub net: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardsCompoundedEvent {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub position: u64,
+    pub gross: u64,
+    pub fee: u64,
+    pub amount: u64,
+    pub new_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CompoundCrankEvent {
+    pub pool: Pubkey,
+    pub keeper: Pubkey,
+    pub positions: u64,
+    pub compounded: u64,
+    pub protocol_fee: u64,
+    pub keeper_tip: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UnstakeEvent {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub route: ExitRoute,
+    pub request: Option<u64>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawalQueuedEvent {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub request: u64,
+    pub amount: u64,
+    pub release_time: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawalCancelledEvent {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub request: u64,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawalQueueProcessedEvent {
+    pub pool: Pubkey,
+    pub processed: u64,
+    pub paid: u64,
+    pub remaining: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct KeeperBudgetFundedEvent {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub budget: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct KeeperCrankEvent {
+    pub pool: Pubkey,
+    pub keeper: Pubkey,
+    pub task: KeeperTask,
+    pub tip: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryTransactionProposedEvent {
+    pub transaction: Pubkey,
+    pub proposer: Pubkey,
+    pub index: u64,
+    pub action: TreasuryAction,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryTransactionApprovedEvent {
+    pub transaction: Pubkey,
+    pub signer: Pubkey,
+    pub approvals: u8,
+    pub threshold: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryTransactionExecutedEvent {
+    pub transaction: Pubkey,
+    pub executor: Pubkey,
+    pub action: TreasuryAction,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryTransactionCancelledEvent {
+    pub transaction: Pubkey,
+    pub signer: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolFeeUpdatedEvent {
+    pub protocol_fee_bps: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryDepositEvent {
+    pub depositor: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub total_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasurySyncedEvent {
+    pub caller: Pubkey,
+    pub mint: Pubkey,
+    pub recorded_balance: u64,
+    pub actual_balance: u64,
+    pub difference: i128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawalLimitUpdatedEvent {
+    pub mint: Pubkey,
+    pub withdrawal_limit: u64,
+    pub withdrawal_period: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OutflowCapUpdatedEvent {
+    pub epoch_outflow_cap: u64,
+    pub epoch_duration: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasurySignersUpdatedEvent {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub owner_set_seqno: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PriceFeedUpdatedEvent {
+    pub feed: Pubkey,
+    pub mint: Pubkey,
+    pub price: i64,
+    pub conf: u64,
+    pub publish_time: i64,
+}
+
+#[event]
+pub struct BondMarketCreatedEvent {
+    pub market: Pubkey,
+    pub quote_mint: Pubkey,
+    pub payout_mint: Pubkey,
+    pub control_variable: u64,
+    pub capacity: u64,
+    pub vesting_term: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ControlVariableAdjustedEvent {
+    pub market: Pubkey,
+    pub control_variable: u64,
+    pub target: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BondCreatedEvent {
+    pub market: Pubkey,
+    pub user: Pubkey,
+    pub bond: u64,
+    pub deposit: u64,
+    pub value: u64,
+    pub payout: u64,
+    pub price: u64,
+    pub maturity: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BondRedeemedEvent {
+    pub market: Pubkey,
+    pub user: Pubkey,
+    pub bond: u64,
+    pub amount: u64,
+    pub vested_paid: u64,
+    pub remaining: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardModeUpdatedEvent {
+    pub pool: Pubkey,
+    pub mode: RewardMode,
+    pub emission_rate: u64,
+    pub step_interval: i64,
+    pub step_percent: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LockTierUpdatedEvent {
+    pub pool: Pubkey,
+    pub tier: u8,
+    pub duration: i64,
+    pub multiplier_bps: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidStakingEnabledEvent {
+    pub pool: Pubkey,
+    pub receipt_mint: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidStakeEvent {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub receipts: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidUnstakeEvent {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub receipts: u64,
+    pub amount: u64,
+    pub release_time: i64,
+    pub timestamp: i64,
+}
@@ -1,198 +1,1891 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount};
+use anchor_lang::solana_program::sysvar;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::error::ProtocolError;
 use crate::state::*;
 
+#[derive(Accounts)]
+#[instruction(config: ProtocolConfig)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TreasuryState::LEN,
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TreasuryLedger::LEN,
+        seeds = [b"treasury_ledger", treasury_mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"treasury_vault", treasury_mint.key().as_ref()],
+        bump,
+        token::mint = treasury_mint,
+        token::authority = treasury_state
+    )]
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + GovernanceState::LEN,
+        seeds = [b"governance_state"],
+        bump
+    )]
+    pub governance_state: Account<'info, GovernanceState>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(config: PoolConfig)]
+pub struct CreatePool<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"governance_state"],
+        bump,
+        constraint = governance_state.authority == authority.key() @ ProtocolError::InvalidAuthority
+    )]
+    pub governance_state: Account<'info, GovernanceState>,
+    
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakingState::LEN,
+        seeds = [b"staking_state", stake_mint.key().as_ref()],
+        bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"reward_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = stake_mint,
+        token::authority = staking_state
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + KeeperState::LEN,
+        seeds = [b"keeper_state", staking_state.key().as_ref()],
+        bump
+    )]
+    pub keeper_state: Account<'info, KeeperState>,
+    
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"keeper_budget", staking_state.key().as_ref()],
+        bump,
+        token::mint = stake_mint,
+        token::authority = staking_state
+    )]
+    pub keeper_budget: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + TreasuryLedger::LEN,
+        seeds = [b"treasury_ledger", stake_mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"treasury_vault", stake_mint.key().as_ref()],
+        bump,
+        token::mint = stake_mint,
+        token::authority = treasury_state
+    )]
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct OpenPosition<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserStakeInfo::LEN,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = 8 + StakePosition::LEN,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &user_stake_info.position_count.to_le_bytes()],
+        bump
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = user
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey, amount: u64)]
+pub struct StakeFor<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + UserStakeInfo::LEN,
+        seeds = [b"user_stake", staking_state.key().as_ref(), beneficiary.as_ref()],
+        bump
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + StakePosition::LEN,
+        seeds = [b"stake_position", staking_state.key().as_ref(), beneficiary.as_ref(), &user_stake_info.position_count.to_le_bytes()],
+        bump
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = payer
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct AddToPosition<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = user_stake_info.owner == user.key()
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = user
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct SplitPosition<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = user_stake_info.owner == user.key()
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = 8 + StakePosition::LEN,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &user_stake_info.position_count.to_le_bytes()],
+        bump
+    )]
+    pub new_position: Account<'info, StakePosition>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MergePositions<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &target.index.to_le_bytes()],
+        bump = target.bump,
+        constraint = target.owner == user.key()
+    )]
+    pub target: Account<'info, StakePosition>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &source.index.to_le_bytes()],
+        bump = source.bump,
+        constraint = source.owner == user.key(),
+        constraint = source.key() != target.key() @ ProtocolError::SamePosition,
+        close = user
+    )]
+    pub source: Account<'info, StakePosition>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key(),
+        constraint = position.is_empty() @ ProtocolError::PositionNotEmpty,
+        close = user
+    )]
+    pub position: Account<'info, StakePosition>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct RequestUnstake<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = user_stake_info.owner == user.key()
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+}
+
+#[derive(Accounts)]
+pub struct CompleteUnstake<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + WithdrawalState::LEN,
+        seeds = [b"withdrawal_state", staking_state.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_state: Account<'info, WithdrawalState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = user_stake_info.owner == user.key()
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = user
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CompoundRewards<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = user_stake_info.owner == user.key()
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"reward_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", staking_state.stake_mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        mut,
+        address = treasury_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetAutoCompound<'info> {
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+}
+
+#[derive(Accounts)]
+pub struct CrankCompound<'info> {
+    pub keeper: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_state", staking_state.key().as_ref()],
+        bump = keeper_state.bump
+    )]
+    pub keeper_state: Account<'info, KeeperState>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_budget", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub keeper_budget: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint
+    )]
+    pub keeper_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"reward_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", staking_state.stake_mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        mut,
+        address = treasury_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateKeeperConfig<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump,
+        constraint = staking_state.authority == authority.key() @ ProtocolError::InvalidAuthority
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_state", staking_state.key().as_ref()],
+        bump = keeper_state.bump
+    )]
+    pub keeper_state: Account<'info, KeeperState>,
+}
+
+#[derive(Accounts)]
+pub struct FundKeeperBudget<'info> {
+    pub funder: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = funder
+    )]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_budget", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub keeper_budget: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CrankRewardIndex<'info> {
+    pub keeper: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_state", staking_state.key().as_ref()],
+        bump = keeper_state.bump
+    )]
+    pub keeper_state: Account<'info, KeeperState>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_budget", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub keeper_budget: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint
+    )]
+    pub keeper_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CrankRewardTopUp<'info> {
+    pub keeper: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", staking_state.stake_mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        mut,
+        address = treasury_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"reward_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_state", staking_state.key().as_ref()],
+        bump = keeper_state.bump
+    )]
+    pub keeper_state: Account<'info, KeeperState>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_budget", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub keeper_budget: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint
+    )]
+    pub keeper_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = user
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"reward_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", staking_state.stake_mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        mut,
+        address = treasury_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct FundRewards<'info> {
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = funder
+    )]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"reward_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ViewStakingState<'info> {
+    #[account(
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+}
+
+#[derive(Accounts)]
+pub struct EnableLiquidStaking<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump,
+        constraint = staking_state.authority == authority.key() @ ProtocolError::InvalidAuthority
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"receipt_mint", staking_state.key().as_ref()],
+        bump,
+        mint::decimals = stake_mint.decimals,
+        mint::authority = staking_state
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct StakeLiquid<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", staking_state.stake_mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        mut,
+        address = treasury_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"receipt_mint", staking_state.key().as_ref()],
+        bump,
+        address = staking_state.receipt_mint @ ProtocolError::LiquidStakingDisabled
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        token::mint = receipt_mint,
+        token::authority = user
+    )]
+    pub user_receipt_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = user
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"reward_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
 
+#[derive(Accounts)]
+#[instruction(receipts: u64)]
+pub struct UnstakeLiquid<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserStakeInfo::LEN,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", staking_state.stake_mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        mut,
+        address = treasury_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"receipt_mint", staking_state.key().as_ref()],
+        bump,
+        address = staking_state.receipt_mint @ ProtocolError::LiquidStakingDisabled
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        token::mint = receipt_mint,
+        token::authority = user
+    )]
+    pub user_receipt_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"reward_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ViewLiquidRate<'info> {
+    #[account(
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(address = staking_state.receipt_mint @ ProtocolError::LiquidStakingDisabled)]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump,
+        constraint = staking_state.authority == authority.key() @ ProtocolError::InvalidAuthority
+    )]
+    pub staking_state: Account<'info, StakingState>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct EmergencyWithdraw<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", staking_state.stake_mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = user_stake_info.owner == user.key()
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = user
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"reward_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        address = treasury_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct Unstake<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + WithdrawalState::LEN,
+        seeds = [b"withdrawal_state", staking_state.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_state: Account<'info, WithdrawalState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = user_stake_info.owner == user.key()
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    // Always created at the queue tail and closed again when the exit is paid instantly.
+    #[account(
+        init,
+        payer = user,
+        space = 8 + WithdrawalRequest::LEN,
+        seeds = [b"withdrawal_request", withdrawal_state.key().as_ref(), &withdrawal_state.queue_tail.to_le_bytes()],
+        bump
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint,
+        token::authority = user
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct JoinWithdrawalQueue<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + WithdrawalState::LEN,
+        seeds = [b"withdrawal_state", staking_state.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_state: Account<'info, WithdrawalState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = user_stake_info.owner == user.key()
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    #[account(
+        init,
+        payer = user,
+        space = 8 + WithdrawalRequest::LEN,
+        seeds = [b"withdrawal_request", withdrawal_state.key().as_ref(), &withdrawal_state.queue_tail.to_le_bytes()],
+        bump
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelWithdrawal<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"withdrawal_state", staking_state.key().as_ref()],
+        bump = withdrawal_state.bump
+    )]
+    pub withdrawal_state: Account<'info, WithdrawalState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_stake", staking_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        constraint = user_stake_info.owner == user.key()
+    )]
+    pub user_stake_info: Account<'info, UserStakeInfo>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_position", staking_state.key().as_ref(), user.key().as_ref(), &position.index.to_le_bytes()],
+        bump = position.bump,
+        constraint = position.owner == user.key()
+    )]
+    pub position: Account<'info, StakePosition>,
+    
+    #[account(
+        mut,
+        seeds = [b"withdrawal_request", withdrawal_state.key().as_ref(), &withdrawal_request.index.to_le_bytes()],
+        bump = withdrawal_request.bump,
+        constraint = withdrawal_request.owner == user.key(),
+        constraint = withdrawal_request.position == position.index @ ProtocolError::InvalidWithdrawalRequest,
+        constraint = !withdrawal_request.cancelled @ ProtocolError::WithdrawalCancelled
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessWithdrawalQueue<'info> {
+    pub keeper: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_state", staking_state.stake_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        seeds = [b"withdrawal_state", staking_state.key().as_ref()],
+        bump = withdrawal_state.bump
+    )]
+    pub withdrawal_state: Account<'info, WithdrawalState>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_state", staking_state.key().as_ref()],
+        bump = keeper_state.bump
+    )]
+    pub keeper_state: Account<'info, KeeperState>,
+    
+    #[account(
+        mut,
+        seeds = [b"keeper_budget", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub keeper_budget: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        token::mint = staking_state.stake_mint
+    )]
+    pub keeper_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"principal_vault", staking_state.key().as_ref()],
+        bump,
+        token::mint = staking_state.stake_mint,
+        token::authority = staking_state
+    )]
+    pub principal_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = staking_state.stake_mint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
 
 #[derive(Accounts)]
-#[instruction(config: ProtocolConfig)]
-pub struct Initialize<'info> {
+pub struct CreatePriceFeed<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
     
+    #[account(
+        seeds = [b"governance_state"],
+        bump,
+        constraint = governance_state.authority == authority.key() @ ProtocolError::InvalidAuthority
+    )]
+    pub governance_state: Account<'info, GovernanceState>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
     #[account(
         init,
         payer = authority,
-        space = 8 + StakingState::LEN,
-        seeds = [b"staking_state"],
+        space = 8 + PriceFeed::LEN,
+        seeds = [b"price_feed", mint.key().as_ref()],
         bump
     )]
-    pub staking_state: Account<'info, StakingState>,
+    pub price_feed: Account<'info, PriceFeed>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePriceFeed<'info> {
+    pub oracle: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"price_feed", price_feed.mint.as_ref()],
+        bump = price_feed.bump,
+        constraint = price_feed.authority == oracle.key() @ ProtocolError::InvalidAuthority
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+}
+
+#[derive(Accounts)]
+pub struct CreateBondMarket<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"governance_state"],
+        bump,
+        constraint = governance_state.authority == authority.key() @ ProtocolError::InvalidAuthority
+    )]
+    pub governance_state: Account<'info, GovernanceState>,
+    
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(constraint = payout_mint.key() != quote_mint.key() @ ProtocolError::InvalidBondConfig)]
+    pub payout_mint: InterfaceAccount<'info, Mint>,
     
     #[account(
         init,
         payer = authority,
-        space = 8 + TreasuryState::LEN,
+        space = 8 + BondMarket::LEN,
+        seeds = [b"bond_market", quote_mint.key().as_ref(), payout_mint.key().as_ref()],
+        bump
+    )]
+    pub bond_market: Account<'info, BondMarket>,
+    
+    #[account(
+        constraint = price_feed.mint == quote_mint.key() @ ProtocolError::InvalidPriceFeed
+    )]
+    pub price_feed: Option<Account<'info, PriceFeed>>,
+    
+    #[account(
         seeds = [b"treasury_state"],
         bump
     )]
     pub treasury_state: Account<'info, TreasuryState>,
     
     #[account(
-        init,
+        init_if_needed,
         payer = authority,
-        space = 8 + GovernanceState::LEN,
-        seeds = [b"governance_state"],
+        space = 8 + TreasuryLedger::LEN,
+        seeds = [b"treasury_ledger", quote_mint.key().as_ref()],
         bump
     )]
-    pub governance_state: Account<'info, GovernanceState>,
+    pub treasury_quote_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"treasury_vault", quote_mint.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = treasury_state
+    )]
+    pub treasury_quote_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + TreasuryLedger::LEN,
+        seeds = [b"treasury_ledger", payout_mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_payout_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"treasury_vault", payout_mint.key().as_ref()],
+        bump,
+        token::mint = payout_mint,
+        token::authority = treasury_state
+    )]
+    pub treasury_payout_vault: InterfaceAccount<'info, TokenAccount>,
     
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(amount: u64)]
-pub struct Stake<'info> {
+pub struct UpdateBondMarket<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"governance_state"],
+        bump,
+        constraint = governance_state.authority == authority.key() @ ProtocolError::InvalidAuthority
+    )]
+    pub governance_state: Account<'info, GovernanceState>,
+    
+    #[account(
+        mut,
+        seeds = [b"bond_market", bond_market.quote_mint.as_ref(), bond_market.payout_mint.as_ref()],
+        bump = bond_market.bump
+    )]
+    pub bond_market: Account<'info, BondMarket>,
+}
+
+#[derive(Accounts)]
+pub struct CreateBond<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     
     #[account(
         mut,
-        seeds = [b"staking_state"],
-        bump
+        seeds = [b"bond_market", bond_market.quote_mint.as_ref(), bond_market.payout_mint.as_ref()],
+        bump = bond_market.bump
     )]
-    pub staking_state: Account<'info, StakingState>,
+    pub bond_market: Account<'info, BondMarket>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", bond_market.quote_mint.as_ref()],
+        bump = treasury_quote_ledger.bump
+    )]
+    pub treasury_quote_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", bond_market.payout_mint.as_ref()],
+        bump = treasury_payout_ledger.bump
+    )]
+    pub treasury_payout_ledger: Account<'info, TreasuryLedger>,
     
     #[account(
         init_if_needed,
         payer = user,
-        space = 8 + UserStakeInfo::LEN,
-        seeds = [b"user_stake", user.key().as_ref()],
+        space = 8 + UserBondInfo::LEN,
+        seeds = [b"user_bonds", bond_market.key().as_ref(), user.key().as_ref()],
         bump
     )]
-    pub user_stake_info: Account<'info, UserStakeInfo>,
+    pub user_bond_info: Account<'info, UserBondInfo>,
     
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Bond::LEN,
+        seeds = [b"bond", bond_market.key().as_ref(), user.key().as_ref(), &user_bond_info.bond_count.to_le_bytes()],
+        bump
+    )]
+    pub bond: Account<'info, Bond>,
     
-    #[account(mut)]
-    pub protocol_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = bond_market.quote_mint,
+        token::authority = user
+    )]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        address = treasury_quote_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_quote_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        address = treasury_payout_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_payout_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(address = bond_market.quote_mint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(address = bond_market.payout_mint)]
+    pub payout_mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(address = bond_market.price_feed @ ProtocolError::InvalidPriceFeed)]
+    pub price_feed: Option<Account<'info, PriceFeed>>,
     
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(amount: u64)]
-pub struct Unstake<'info> {
+pub struct RedeemBond<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     
     #[account(
         mut,
-        seeds = [b"staking_state"],
+        seeds = [b"bond_market", bond_market.quote_mint.as_ref(), bond_market.payout_mint.as_ref()],
+        bump = bond_market.bump
+    )]
+    pub bond_market: Account<'info, BondMarket>,
+    
+    #[account(
+        seeds = [b"treasury_state"],
         bump
     )]
-    pub staking_state: Account<'info, StakingState>,
+    pub treasury_state: Account<'info, TreasuryState>,
     
     #[account(
         mut,
-        constraint = user_stake_info.owner == user.key()
+        seeds = [b"treasury_ledger", bond_market.payout_mint.as_ref()],
+        bump = treasury_payout_ledger.bump
     )]
-    pub user_stake_info: Account<'info, UserStakeInfo>,
+    pub treasury_payout_ledger: Account<'info, TreasuryLedger>,
     
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"bond", bond_market.key().as_ref(), user.key().as_ref(), &bond.index.to_le_bytes()],
+        bump = bond.bump,
+        constraint = bond.owner == user.key()
+    )]
+    pub bond: Account<'info, Bond>,
     
-    #[account(mut)]
-    pub protocol_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = bond_market.payout_mint,
+        token::authority = user
+    )]
+    pub user_payout_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        address = treasury_payout_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_payout_account: InterfaceAccount<'info, TokenAccount>,
     
-    pub token_program: Program<'info, Token>,
+    #[account(address = bond_market.payout_mint)]
+    pub payout_mint: InterfaceAccount<'info, Mint>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct ClaimRewards<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+pub struct DepositToTreasury<'info> {
+    pub depositor: Signer<'info>,
     
     #[account(
         mut,
-        seeds = [b"staking_state"],
-        bump
+        seeds = [b"treasury_ledger", treasury_ledger.mint.as_ref()],
+        bump = treasury_ledger.bump
     )]
-    pub staking_state: Account<'info, StakingState>,
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
     
     #[account(
         mut,
-        constraint = user_stake_info.owner == user.key()
+        token::mint = treasury_ledger.mint,
+        token::authority = depositor
     )]
-    pub user_stake_info: Account<'info, UserStakeInfo>,
+    pub depositor_token_account: InterfaceAccount<'info, TokenAccount>,
     
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        address = treasury_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
     
-    #[account(mut)]
-    pub protocol_token_account: Account<'info, TokenAccount>,
+    #[account(address = treasury_ledger.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
     
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateConfig<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+pub struct SyncTreasury<'info> {
+    pub caller: Signer<'info>,
     
     #[account(
         mut,
-        constraint = staking_state.authority == authority.key()
+        seeds = [b"treasury_ledger", treasury_ledger.mint.as_ref()],
+        bump = treasury_ledger.bump
     )]
-    pub staking_state: Account<'info, StakingState>,
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+    
+    #[account(
+        address = treasury_ledger.vault @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
 }
 
 #[derive(Accounts)]
-pub struct EmergencyWithdraw<'info> {
+pub struct ViewTreasuryLedger<'info> {
+    #[account(
+        seeds = [b"treasury_ledger", treasury_ledger.mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Account<'info, TreasuryLedger>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeTreasuryTransaction<'info> {
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub proposer: Signer<'info>,
     
     #[account(
         mut,
-        constraint = staking_state.authority == authority.key()
+        seeds = [b"treasury_state"],
+        bump
     )]
-    pub staking_state: Account<'info, StakingState>,
-    
-    #[account(mut)]
-    pub protocol_token_account: Account<'info, TokenAccount>,
+    pub treasury_state: Account<'info, TreasuryState>,
     
-    #[account(mut)]
-    pub destination_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + TreasuryTransaction::LEN,
+        seeds = [b"treasury_tx", treasury_state.key().as_ref(), &treasury_state.transaction_count.to_le_bytes()],
+        bump
+    )]
+    pub transaction: Account<'info, TreasuryTransaction>,
     
-    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct JoinWithdrawalQueue<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+pub struct ApproveTreasuryTransaction<'info> {
+    pub signer: Signer<'info>,
     
-    #[account(mut)]
-    pub withdrawal_state: Account<'info, WithdrawalState>,
+    #[account(
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
     
     #[account(
         mut,
-        constraint = user_stake_info.owner == user.key()
+        seeds = [b"treasury_tx", treasury_state.key().as_ref(), &transaction.index.to_le_bytes()],
+        bump = transaction.bump
     )]
-    pub user_stake_info: Account<'info, UserStakeInfo>,
+    pub transaction: Account<'info, TreasuryTransaction>,
 }
 
 #[derive(Accounts)]
-pub struct ProcessWithdrawalQueue<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+pub struct ExecuteTreasuryTransaction<'info> {
+    pub executor: Signer<'info>,
     
     #[account(
         mut,
-        seeds = [b"withdrawal_state"],
+        seeds = [b"treasury_state"],
         bump
     )]
-    pub withdrawal_state: Account<'info, WithdrawalState>,
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        seeds = [b"treasury_tx", treasury_state.key().as_ref(), &transaction.index.to_le_bytes()],
+        bump = transaction.bump
+    )]
+    pub transaction: Account<'info, TreasuryTransaction>,
+    
+    // Transfer and reward top-up actions only
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", treasury_ledger.mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Option<Account<'info, TreasuryLedger>>,
+    
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == treasury_state.key() @ ProtocolError::InvalidTreasuryAccount
+    )]
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
     
     #[account(mut)]
-    pub protocol_token_account: Account<'info, TokenAccount>,
+    pub destination: Option<InterfaceAccount<'info, TokenAccount>>,
     
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    
+    // Reward top-up actions only
     #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub keeper_state: Option<Account<'info, KeeperState>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTreasuryTransaction<'info> {
+    pub signer: Signer<'info>,
+    
+    #[account(
+        seeds = [b"treasury_state"],
+        bump
+    )]
+    pub treasury_state: Account<'info, TreasuryState>,
+    
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"treasury_tx", treasury_state.key().as_ref(), &transaction.index.to_le_bytes()],
+        bump = transaction.bump,
+        has_one = proposer
+    )]
+    pub transaction: Account<'info, TreasuryTransaction>,
     
-    pub token_program: Program<'info, Token>,
+    /// CHECK: Rent refund destination, checked against the transaction's proposer
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
@@ -221,11 +1914,21 @@ pub struct Vote<'info> {
     #[account(mut)]
     pub proposal: Account<'info, Proposal>,
     
-    #[account(mut)]
+    #[account(
+        seeds = [b"governance_state"],
+        bump
+    )]
     pub governance_state: Account<'info, GovernanceState>,
     
     #[account(
-        constraint = user_stake_info.owner == voter.key()
+        seeds = [b"staking_state", governance_state.voting_mint.as_ref()],
+        bump = staking_state.bump
+    )]
+    pub staking_state: Account<'info, StakingState>,
+    
+    #[account(
+        seeds = [b"user_stake", staking_state.key().as_ref(), voter.key().as_ref()],
+        bump
     )]
     pub user_stake_info: Account<'info, UserStakeInfo>,
 }
@@ -242,10 +1945,18 @@ pub struct ExecuteProposal<'info> {
     pub governance_state: Account<'info, GovernanceState>,
     
     #[account(mut)]
-    pub staking_state: Account<'info, StakingState>,
+    pub staking_state: Option<Account<'info, StakingState>>,
     
     #[account(mut)]
     pub treasury_state: Account<'info, TreasuryState>,
+    
+    // Withdrawal limit proposals only
+    #[account(
+        mut,
+        seeds = [b"treasury_ledger", treasury_ledger.mint.as_ref()],
+        bump = treasury_ledger.bump
+    )]
+    pub treasury_ledger: Option<Account<'info, TreasuryLedger>>,
 }
 
 #[account]
@@ -264,10 +1975,26 @@ pub struct Proposal {
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub enum ProposalData {
-    UpdateApy { new_apy: u64 },
-    UpdateWithdrawalLimit { new_limit: u64 },
+    UpdateApy { pool: Pubkey, new_apy: u64 },
+    UpdateWithdrawalLimit { mint: Pubkey, new_limit: u64 },
     UpdateVotingPeriod { new_period: i64 },
     UpdateQuorum { new_quorum: u64 },
+    UpdateRewardMode {
+        pool: Pubkey,
+        mode: RewardMode,
+        emission_rate: u64,
+        step_interval: i64,
+        step_percent: u64,
+    },
+    UpdateLockTier {
+        pool: Pubkey,
+        tier: u8,
+        duration: i64,
+        multiplier_bps: u64,
+    },
+    UpdateOutflowCap { new_cap: u64, new_epoch_duration: i64 },
+    UpdateWithdrawalPeriod { mint: Pubkey, new_period: i64 },
+    UpdateProtocolFee { new_fee_bps: u64 },
 }
 
 impl Proposal {
@@ -281,4 +2008,4 @@ impl Proposal {
             
         total_votes >= required_quorum && self.for_votes > self.against_votes
     }
-}
\ No newline at end of file
+}
@@ -1,12 +1,18 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Transfer};
+use anchor_lang::solana_program::instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT};
+use anchor_lang::solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
+use anchor_spl::token_interface::{self, Burn, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked};
 
+pub mod constants;
 pub mod error;
+pub mod events;
 pub mod state;
 pub mod instructions;
 
+use crate::constants::*;
 use crate::instructions::*;
 use crate::error::ProtocolError;
+use crate::events::*;
 use crate::state::*;
 
 declare_id!("EMmqYXyEiJuBqSQFkpsXJLPPZVj6LbiaThEyMNgrYzXD");
@@ -16,155 +22,1795 @@ pub mod death_protocol {
     use super::*;
 
     pub fn initialize(ctx: Context<Initialize>, config: ProtocolConfig) -> Result<()> {
-        let staking_state = &mut ctx.accounts.staking_state;
         let treasury_state = &mut ctx.accounts.treasury_state;
         let governance_state = &mut ctx.accounts.governance_state;
 
-        staking_state.authority = ctx.accounts.authority.key();
-        staking_state.current_apy = config.base_apy;
-        staking_state.min_stake = config.min_stake;
-        staking_state.max_stake = config.max_stake;
-        staking_state.emergency_cooldown = config.emergency_cooldown;
-        staking_state.paused = false;
-        staking_state.total_staked = 0;
+        treasury_state.authority = ctx.accounts.authority.key();
+        treasury_state.set_protocol_fee(config.protocol_fee_bps)?;
+        let current_time = Clock::get()?.unix_timestamp;
+        treasury_state.set_outflow_cap(config.epoch_outflow_cap, config.epoch_duration, current_time)?;
+        treasury_state.set_signers(&config.treasury_signers, config.required_signatures)?;
+        treasury_state.transaction_count = 0;
+
+        open_treasury_ledger(
+            &mut ctx.accounts.treasury_ledger,
+            &ctx.accounts.treasury_vault,
+            ctx.bumps.treasury_ledger,
+        );
+        let treasury_ledger = &mut ctx.accounts.treasury_ledger;
+        treasury_ledger.set_withdrawal_limit(config.withdrawal_limit, config.withdrawal_period)?;
+        treasury_ledger.window_start = current_time;
+
+        governance_state.authority = ctx.accounts.authority.key();
+        governance_state.voting_period = config.voting_period;
+        governance_state.required_quorum = 100;
+        governance_state.proposal_count = 0;
+        governance_state.pool_count = 0;
+        // Votes are weighted by stake in the pool for the treasury's base mint.
+        governance_state.voting_mint = ctx.accounts.treasury_mint.key();
+
+        Ok(())
+    }
+
+    pub fn create_pool(ctx: Context<CreatePool>, config: PoolConfig) -> Result<()> {
+        let staking_state = &mut ctx.accounts.staking_state;
+        let current_time = Clock::get()?.unix_timestamp;
+
+        staking_state.authority = ctx.accounts.authority.key();
+        staking_state.stake_mint = ctx.accounts.stake_mint.key();
+        staking_state.current_apy = config.base_apy;
+        staking_state.min_stake = config.min_stake;
+        staking_state.max_stake = config.max_stake;
+        staking_state.emergency_cooldown = config.emergency_cooldown;
+        staking_state.paused = false;
+        staking_state.total_staked = 0;
+        staking_state.total_effective_stake = 0;
+        staking_state.total_unbonding = 0;
+        staking_state.reward_per_token = 0;
+        staking_state.last_reward_update = current_time;
+        staking_state.emergency_penalty = config.emergency_penalty;
+        staking_state.penalty_destination = config.penalty_destination;
+        staking_state.unbonding_period = config.unbonding_period;
+        staking_state.keeper_tip_bps = config.keeper_tip_bps;
+        staking_state.liquidity_buffer_bps = config.liquidity_buffer_bps;
+        staking_state.liquidity_buffer_floor = config.liquidity_buffer_floor;
+        staking_state.reward_reserve = 0;
+        staking_state.total_rewards_distributed = 0;
+        staking_state.reward_mode = RewardMode::Apy;
+        staking_state.emission_rate = 0;
+        staking_state.emission_step_interval = 0;
+        staking_state.emission_step_percent = 0;
+        staking_state.next_emission_step = 0;
+        staking_state.bump = ctx.bumps.staking_state;
+        staking_state.receipt_mint = Pubkey::default();
+        staking_state.liquid_staked = 0;
+        staking_state.liquid_reward_per_token_paid = 0;
+        staking_state.liquid_pending_rewards = 0;
+        staking_state.lock_tiers = StakingState::default_lock_tiers();
+        staking_state.approved_depositors = [Pubkey::default(); MAX_APPROVED_DEPOSITORS];
+        staking_state.validate_config()?;
+
+        let keeper_state = &mut ctx.accounts.keeper_state;
+        keeper_state.pool = staking_state.key();
+        keeper_state.apply_config(&config.keeper)?;
+        keeper_state.reward_topup_amount = 0;
+        keeper_state.reward_topup_threshold = 0;
+        keeper_state.last_queue_process = 0;
+        keeper_state.last_reward_update = 0;
+        keeper_state.last_reward_topup = 0;
+        keeper_state.last_compound = 0;
+        keeper_state.total_tips_paid = 0;
+        keeper_state.bump = ctx.bumps.keeper_state;
+
+        open_treasury_ledger(
+            &mut ctx.accounts.treasury_ledger,
+            &ctx.accounts.treasury_vault,
+            ctx.bumps.treasury_ledger,
+        );
+
+        let governance_state = &mut ctx.accounts.governance_state;
+        governance_state.pool_count = governance_state.pool_count
+            .checked_add(1)
+            .ok_or(ProtocolError::Overflow)?;
+
+        emit!(PoolCreatedEvent {
+            pool: staking_state.key(),
+            stake_mint: staking_state.stake_mint,
+            authority: staking_state.authority,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, update: ConfigUpdate) -> Result<()> {
+        let staking_state = &mut ctx.accounts.staking_state;
+        let current_time = Clock::get()?.unix_timestamp;
+
+        if let Some(base_apy) = update.base_apy {
+            staking_state.update_reward_index(current_time)?;
+            staking_state.current_apy = base_apy;
+        }
+        if let Some(min_stake) = update.min_stake {
+            staking_state.min_stake = min_stake;
+        }
+        if let Some(max_stake) = update.max_stake {
+            staking_state.max_stake = max_stake;
+        }
+        if let Some(emergency_cooldown) = update.emergency_cooldown {
+            staking_state.emergency_cooldown = emergency_cooldown;
+        }
+        if let Some(emergency_penalty) = update.emergency_penalty {
+            staking_state.emergency_penalty = emergency_penalty;
+        }
+        if let Some(penalty_destination) = update.penalty_destination {
+            staking_state.penalty_destination = penalty_destination;
+        }
+        if let Some(unbonding_period) = update.unbonding_period {
+            staking_state.unbonding_period = unbonding_period;
+        }
+        if let Some(keeper_tip_bps) = update.keeper_tip_bps {
+            staking_state.keeper_tip_bps = keeper_tip_bps;
+        }
+        if let Some(liquidity_buffer_bps) = update.liquidity_buffer_bps {
+            staking_state.liquidity_buffer_bps = liquidity_buffer_bps;
+        }
+        if let Some(liquidity_buffer_floor) = update.liquidity_buffer_floor {
+            staking_state.liquidity_buffer_floor = liquidity_buffer_floor;
+        }
+
+        staking_state.validate_config()?;
+
+        emit!(ConfigUpdateEvent {
+            pool: staking_state.key(),
+            authority: ctx.accounts.authority.key(),
+            current_apy: staking_state.current_apy,
+            min_stake: staking_state.min_stake,
+            max_stake: staking_state.max_stake,
+            emergency_cooldown: staking_state.emergency_cooldown,
+            emergency_penalty: staking_state.emergency_penalty,
+            unbonding_period: staking_state.unbonding_period,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // Pausing only blocks new stakes. Unbonding, emergency exits and claims
+    // stay open so that user funds are never locked by a pause.
+    pub fn pause(ctx: Context<UpdateConfig>) -> Result<()> {
+        let staking_state = &mut ctx.accounts.staking_state;
+        require!(!staking_state.paused, ProtocolError::ProtocolPaused);
+
+        staking_state.paused = true;
+
+        emit!(ProtocolPausedEvent {
+            pool: ctx.accounts.staking_state.key(),
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn unpause(ctx: Context<UpdateConfig>) -> Result<()> {
+        let staking_state = &mut ctx.accounts.staking_state;
+        require!(staking_state.paused, ProtocolError::ProtocolNotPaused);
+
+        staking_state.paused = false;
+
+        emit!(ProtocolUnpausedEvent {
+            pool: ctx.accounts.staking_state.key(),
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn open_position(ctx: Context<OpenPosition>, amount: u64, lock_tier: u8) -> Result<()> {
+        require!(!ctx.accounts.staking_state.paused, ProtocolError::ProtocolPaused);
+        require!(
+            amount >= ctx.accounts.staking_state.min_stake && 
+            amount <= ctx.accounts.staking_state.max_stake,
+            ProtocolError::InvalidStakeAmount
+        );
+        let tier = ctx.accounts.staking_state.lock_tier(lock_tier)?;
+
+        let received = transfer_to_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.user_token_account,
+            &mut ctx.accounts.principal_vault,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.user,
+            amount,
+        )?;
+        require!(received > 0, ProtocolError::InvalidStakeAmount);
+
+        init_position(
+            &mut ctx.accounts.user_stake_info,
+            &mut ctx.accounts.position,
+            ctx.accounts.user.key(),
+            ctx.accounts.staking_state.key(),
+            lock_tier,
+            tier,
+            ctx.bumps.position,
+        )?;
+
+        deposit_into_position(
+            &mut ctx.accounts.staking_state,
+            &mut ctx.accounts.user_stake_info,
+            &mut ctx.accounts.position,
+            received,
+            Clock::get()?.unix_timestamp,
+        )
+    }
+
+    pub fn stake_for(ctx: Context<StakeFor>, beneficiary: Pubkey, amount: u64, lock_tier: u8) -> Result<()> {
+        require!(!ctx.accounts.staking_state.paused, ProtocolError::ProtocolPaused);
+        require!(
+            amount >= ctx.accounts.staking_state.min_stake && 
+            amount <= ctx.accounts.staking_state.max_stake,
+            ProtocolError::InvalidStakeAmount
+        );
+        let tier = ctx.accounts.staking_state.lock_tier(lock_tier)?;
+
+        let caller_program = calling_program(&ctx.accounts.instructions)?;
+        if let Some(program) = caller_program {
+            require!(
+                ctx.accounts.staking_state.is_approved_depositor(&program),
+                ProtocolError::UnapprovedDepositor
+            );
+        }
+
+        let received = transfer_to_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.payer_token_account,
+            &mut ctx.accounts.principal_vault,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.payer,
+            amount,
+        )?;
+        require!(received > 0, ProtocolError::InvalidStakeAmount);
+
+        init_position(
+            &mut ctx.accounts.user_stake_info,
+            &mut ctx.accounts.position,
+            beneficiary,
+            ctx.accounts.staking_state.key(),
+            lock_tier,
+            tier,
+            ctx.bumps.position,
+        )?;
+
+        let current_time = Clock::get()?.unix_timestamp;
+        deposit_into_position(
+            &mut ctx.accounts.staking_state,
+            &mut ctx.accounts.user_stake_info,
+            &mut ctx.accounts.position,
+            received,
+            current_time,
+        )?;
+
+        emit!(StakeForEvent {
+            pool: ctx.accounts.staking_state.key(),
+            payer: ctx.accounts.payer.key(),
+            beneficiary,
+            caller_program,
+            position: ctx.accounts.position.index,
+            amount: received,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_depositor_allowlist(ctx: Context<UpdateConfig>, programs: Vec<Pubkey>) -> Result<()> {
+        let staking_state = &mut ctx.accounts.staking_state;
+        staking_state.set_approved_depositors(&programs)?;
+
+        emit!(DepositorAllowlistUpdatedEvent {
+            pool: staking_state.key(),
+            authority: ctx.accounts.authority.key(),
+            programs,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn add_to_position(ctx: Context<AddToPosition>, amount: u64) -> Result<()> {
+        require!(!ctx.accounts.staking_state.paused, ProtocolError::ProtocolPaused);
+
+        let new_amount = ctx.accounts.position.amount
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        require!(
+            amount > 0 && new_amount <= ctx.accounts.staking_state.max_stake,
+            ProtocolError::InvalidStakeAmount
+        );
+
+        let received = transfer_to_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.user_token_account,
+            &mut ctx.accounts.principal_vault,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.user,
+            amount,
+        )?;
+        require!(received > 0, ProtocolError::InvalidStakeAmount);
+
+        deposit_into_position(
+            &mut ctx.accounts.staking_state,
+            &mut ctx.accounts.user_stake_info,
+            &mut ctx.accounts.position,
+            received,
+            Clock::get()?.unix_timestamp,
+        )
+    }
+
+    pub fn split_position(ctx: Context<SplitPosition>, amount: u64) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        let min_stake = ctx.accounts.staking_state.min_stake;
+
+        require!(
+            amount >= min_stake && position.amount.saturating_sub(amount) >= min_stake,
+            ProtocolError::InvalidSplitAmount
+        );
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let reward_per_token = ctx.accounts.staking_state.update_reward_index(current_time)?;
+        let loyalty_bonus = ctx.accounts.staking_state.loyalty_bonus_active();
+        position.settle_rewards(current_time, reward_per_token, loyalty_bonus)?;
+        let weight_before = position.effective_stake()?;
+        position.amount = position.amount
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        // The split-off stake keeps its original start time and lock; accrued rewards stay behind.
+        let new_position = &mut ctx.accounts.new_position;
+        new_position.owner = ctx.accounts.user.key();
+        new_position.pool = ctx.accounts.staking_state.key();
+        new_position.index = ctx.accounts.user_stake_info.next_position_index()?;
+        new_position.amount = amount;
+        new_position.start_time = position.start_time;
+        new_position.lock_end = position.lock_end;
+        new_position.lock_tier = position.lock_tier;
+        new_position.lock_duration = position.lock_duration;
+        new_position.multiplier_bps = position.multiplier_bps;
+        new_position.auto_compound = position.auto_compound;
+        new_position.pending_rewards = 0;
+        new_position.rewards_claimed = 0;
+        new_position.bump = ctx.bumps.new_position;
+        new_position.checkpoint(reward_per_token);
+
+        // Flooring each half separately can shave a unit off the combined weight.
+        let weight_after = position.effective_stake()?
+            .checked_add(new_position.effective_stake()?)
+            .ok_or(ProtocolError::Overflow)?;
+        ctx.accounts.staking_state.reweight_stake(weight_before, weight_after)?;
+
+        Ok(())
+    }
+
+    pub fn merge_positions(ctx: Context<MergePositions>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let reward_per_token = ctx.accounts.staking_state.update_reward_index(current_time)?;
+        let loyalty_bonus = ctx.accounts.staking_state.loyalty_bonus_active();
+
+        let source = &mut ctx.accounts.source;
+        source.settle_rewards(current_time, reward_per_token, loyalty_bonus)?;
+
+        let target = &mut ctx.accounts.target;
+        target.settle_rewards(current_time, reward_per_token, loyalty_bonus)?;
+        let weight_before = target.effective_stake()?
+            .checked_add(source.effective_stake()?)
+            .ok_or(ProtocolError::Overflow)?;
+        target.absorb(source)?;
+        ctx.accounts.staking_state.reweight_stake(weight_before, target.effective_stake()?)?;
+
+        Ok(())
+    }
+
+    pub fn close_position(_ctx: Context<ClosePosition>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn request_unstake(ctx: Context<RequestUnstake>, amount: u64) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        withdraw_from_position(
+            &mut ctx.accounts.staking_state,
+            &mut ctx.accounts.user_stake_info,
+            &mut ctx.accounts.position,
+            amount,
+            current_time,
+        )?;
+
+        let release_time = current_time
+            .checked_add(ctx.accounts.staking_state.unbonding_period)
+            .ok_or(ProtocolError::Overflow)?;
+        ctx.accounts.user_stake_info.start_unbonding(amount, release_time)?;
+        ctx.accounts.staking_state.add_unbonding(amount)
+    }
+
+    // Pays out immediately while the vault still covers pending exits and the pool's
+    // liquidity buffer and the epoch outflow cap allows it. Otherwise the exit joins
+    // the withdrawal queue.
+    pub fn unstake(ctx: Context<Unstake>, amount: u64) -> Result<UnstakeOutcome> {
+        let current_time = Clock::get()?.unix_timestamp;
+        withdraw_from_position(
+            &mut ctx.accounts.staking_state,
+            &mut ctx.accounts.user_stake_info,
+            &mut ctx.accounts.position,
+            amount,
+            current_time,
+        )?;
+
+        let withdrawal_state = &mut ctx.accounts.withdrawal_state;
+        withdrawal_state.pool = ctx.accounts.staking_state.key();
+        withdrawal_state.bump = ctx.bumps.withdrawal_state;
+
+        let instant = withdrawal_state.total_queued == 0
+            && ctx.accounts.staking_state.exit_covered(
+                ctx.accounts.principal_vault.amount,
+                amount,
+                withdrawal_state.total_queued,
+            )?
+            && amount <= ctx.accounts.treasury_state.outflow_headroom(current_time)?;
+
+        let outcome = if instant {
+            ctx.accounts.treasury_state.record_outflow(amount, current_time)?;
+            transfer_from_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.principal_vault,
+                &mut ctx.accounts.user_token_account,
+                &ctx.accounts.stake_mint,
+                &ctx.accounts.staking_state,
+                amount,
+            )?;
+            ctx.accounts.withdrawal_request.close(ctx.accounts.user.to_account_info())?;
+
+            UnstakeOutcome {
+                route: ExitRoute::Instant,
+                amount,
+                request: None,
+                release_time: current_time,
+            }
+        } else {
+            let release_time = current_time
+                .checked_add(ctx.accounts.staking_state.unbonding_period)
+                .ok_or(ProtocolError::Overflow)?;
+            ctx.accounts.withdrawal_request.bump = ctx.bumps.withdrawal_request;
+            enqueue_withdrawal(
+                &mut ctx.accounts.withdrawal_state,
+                &mut ctx.accounts.withdrawal_request,
+                ctx.accounts.user.key(),
+                ctx.accounts.position.index,
+                amount,
+                current_time,
+                release_time,
+            )?;
+
+            UnstakeOutcome {
+                route: ExitRoute::Queued,
+                amount,
+                request: Some(ctx.accounts.withdrawal_request.index),
+                release_time,
+            }
+        };
+
+        emit!(UnstakeEvent {
+            pool: ctx.accounts.staking_state.key(),
+            user: ctx.accounts.user.key(),
+            amount,
+            route: outcome.route,
+            request: outcome.request,
+            timestamp: current_time,
+        });
+
+        Ok(outcome)
+    }
+
+    pub fn complete_unstake(ctx: Context<CompleteUnstake>) -> Result<()> {
+        let user_info = &mut ctx.accounts.user_stake_info;
+        let amount = user_info.unbonding_amount;
+
+        require!(amount > 0, ProtocolError::NothingUnbonding);
+
+        let current_time = Clock::get()?.unix_timestamp;
+        require!(
+            current_time >= user_info.unbonding_release_time,
+            ProtocolError::UnbondingPeriodActive
+        );
+
+        let withdrawal_state = &mut ctx.accounts.withdrawal_state;
+        withdrawal_state.pool = ctx.accounts.staking_state.key();
+        withdrawal_state.bump = ctx.bumps.withdrawal_state;
+
+        let staking_state = &mut ctx.accounts.staking_state;
+        staking_state.release_unbonding(amount)?;
+        require!(
+            staking_state.exit_covered(
+                ctx.accounts.principal_vault.amount,
+                amount,
+                withdrawal_state.total_queued,
+            )?,
+            ProtocolError::InsufficientBalance
+        );
+        ctx.accounts.treasury_state.record_outflow(amount, current_time)?;
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.principal_vault,
+            &mut ctx.accounts.user_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            amount,
+        )?;
+
+        user_info.unbonding_amount = 0;
+        user_info.unbonding_release_time = 0;
+
+        Ok(())
+    }
+
+    pub fn join_withdrawal_queue(ctx: Context<JoinWithdrawalQueue>, amount: u64) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        withdraw_from_position(
+            &mut ctx.accounts.staking_state,
+            &mut ctx.accounts.user_stake_info,
+            &mut ctx.accounts.position,
+            amount,
+            current_time,
+        )?;
+
+        let withdrawal_state = &mut ctx.accounts.withdrawal_state;
+        withdrawal_state.pool = ctx.accounts.staking_state.key();
+        withdrawal_state.bump = ctx.bumps.withdrawal_state;
+
+        let release_time = current_time
+            .checked_add(ctx.accounts.staking_state.unbonding_period)
+            .ok_or(ProtocolError::Overflow)?;
+        ctx.accounts.withdrawal_request.bump = ctx.bumps.withdrawal_request;
+        enqueue_withdrawal(
+            withdrawal_state,
+            &mut ctx.accounts.withdrawal_request,
+            ctx.accounts.user.key(),
+            ctx.accounts.position.index,
+            amount,
+            current_time,
+            release_time,
+        )?;
+
+        let request = &ctx.accounts.withdrawal_request;
+        emit!(WithdrawalQueuedEvent {
+            pool: ctx.accounts.staking_state.key(),
+            user: request.owner,
+            request: request.index,
+            amount,
+            release_time: request.release_time,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // The request account stays in place so the queue has no gaps; the next
+    // process call skips it and returns its rent to the owner.
+    pub fn cancel_withdrawal(ctx: Context<CancelWithdrawal>) -> Result<()> {
+        let request = &mut ctx.accounts.withdrawal_request;
+        let amount = request.amount;
+        let current_time = Clock::get()?.unix_timestamp;
+
+        let reward_per_token = ctx.accounts.staking_state.update_reward_index(current_time)?;
+        let loyalty_bonus = ctx.accounts.staking_state.loyalty_bonus_active();
+        let position = &mut ctx.accounts.position;
+        position.settle_rewards(current_time, reward_per_token, loyalty_bonus)?;
+        let weight_before = position.effective_stake()?;
+        position.amount = position.amount
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        let user_info = &mut ctx.accounts.user_stake_info;
+        user_info.staked_amount = user_info.staked_amount
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        let staking_state = &mut ctx.accounts.staking_state;
+        staking_state.total_staked = staking_state.total_staked
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        staking_state.reweight_stake(weight_before, position.effective_stake()?)?;
+
+        ctx.accounts.withdrawal_state.release(amount)?;
+        request.cancelled = true;
+
+        emit!(WithdrawalCancelledEvent {
+            pool: staking_state.key(),
+            user: request.owner,
+            request: request.index,
+            amount,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // Remaining accounts are (withdrawal_request, owner token account, owner) triples
+    // starting at the queue head. Processing stops at the first request still unbonding.
+    pub fn process_withdrawal_queue<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessWithdrawalQueue<'info>>,
+    ) -> Result<()> {
+        let triples = ctx.remaining_accounts.chunks_exact(3);
+        require!(
+            triples.remainder().is_empty() && triples.len() <= MAX_BATCH_SIZE,
+            ProtocolError::InvalidWithdrawalRequest
+        );
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let tip_due = ctx.accounts.keeper_state.record_run(KeeperTask::ProcessQueue, current_time)?;
+        let queue = ctx.accounts.withdrawal_state.key();
+        let mut processed = 0u64;
+        let mut paid = 0u64;
+
+        for accounts in triples {
+            let request = Account::<WithdrawalRequest>::try_from(&accounts[0])?;
+            require!(
+                request.queue == queue && request.index == ctx.accounts.withdrawal_state.queue_head,
+                ProtocolError::InvalidWithdrawalRequest
+            );
+            require!(accounts[2].key() == request.owner, ProtocolError::InvalidWithdrawalRequest);
+
+            if !request.is_due(current_time) {
+                break;
+            }
+
+            if !request.cancelled {
+                if request.amount > ctx.accounts.treasury_state.outflow_headroom(current_time)? {
+                    break;
+                }
+                ctx.accounts.treasury_state.record_outflow(request.amount, current_time)?;
+
+                let mut destination = InterfaceAccount::<TokenAccount>::try_from(&accounts[1])?;
+                require!(
+                    destination.owner == request.owner
+                        && destination.mint == ctx.accounts.staking_state.stake_mint,
+                    ProtocolError::InvalidWithdrawalRequest
+                );
+
+                transfer_from_vault(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.principal_vault,
+                    &mut destination,
+                    &ctx.accounts.stake_mint,
+                    &ctx.accounts.staking_state,
+                    request.amount,
+                )?;
+                ctx.accounts.withdrawal_state.release(request.amount)?;
+                paid = paid
+                    .checked_add(request.amount)
+                    .ok_or(ProtocolError::Overflow)?;
+            }
+
+            request.close(accounts[2].clone())?;
+            ctx.accounts.withdrawal_state.advance_head()?;
+            processed += 1;
+        }
+
+        require!(processed > 0, ProtocolError::NothingToProcess);
+
+        let withdrawal_state = &mut ctx.accounts.withdrawal_state;
+        withdrawal_state.last_processed_time = current_time;
+
+        emit!(WithdrawalQueueProcessedEvent {
+            pool: ctx.accounts.staking_state.key(),
+            processed,
+            paid,
+            remaining: withdrawal_state.pending_requests(),
+            timestamp: current_time,
+        });
+
+        let tip = pay_keeper_tip(
+            &ctx.accounts.token_program,
+            &ctx.accounts.keeper_budget,
+            &mut ctx.accounts.keeper_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            &mut ctx.accounts.keeper_state,
+            tip_due,
+        )?;
+
+        emit!(KeeperCrankEvent {
+            pool: ctx.accounts.staking_state.key(),
+            keeper: ctx.accounts.keeper.key(),
+            task: KeeperTask::ProcessQueue,
+            tip,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>, amount: u64) -> Result<()> {
+        let user_info = &mut ctx.accounts.user_stake_info;
+        let current_time = Clock::get()?.unix_timestamp;
+
+        require!(
+            user_info.can_emergency_withdraw(current_time, ctx.accounts.staking_state.emergency_cooldown),
+            ProtocolError::EmergencyWithdrawCooldown
+        );
+
+        let position = &mut ctx.accounts.position;
+        require!(
+            amount > 0 && amount <= position.amount,
+            ProtocolError::InsufficientBalance
+        );
+
+        let reward_per_token = ctx.accounts.staking_state.update_reward_index(current_time)?;
+        let loyalty_bonus = ctx.accounts.staking_state.loyalty_bonus_active();
+        position.settle_rewards(current_time, reward_per_token, loyalty_bonus)?;
+        let weight_before = position.effective_stake()?;
+        position.amount = position.amount
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        let weight_after = position.effective_stake()?;
+
+        let penalty = ctx.accounts.staking_state.calculate_emergency_penalty(amount)?;
+        let withdrawal_amount = amount.checked_sub(penalty)
+            .ok_or(ProtocolError::Overflow)?;
+        ctx.accounts.treasury_state.record_outflow(withdrawal_amount, current_time)?;
+
+        user_info.staked_amount = user_info.staked_amount
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        user_info.last_emergency_withdraw = current_time;
+
+        let staking_state = &mut ctx.accounts.staking_state;
+        staking_state.total_staked = staking_state.total_staked
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        staking_state.reweight_stake(weight_before, weight_after)?;
+
+        let penalty_to_stakers = penalty > 0
+            && staking_state.penalty_destination == PenaltyDestination::Stakers
+            && staking_state.total_effective_stake > 0;
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.principal_vault,
+            &mut ctx.accounts.user_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            withdrawal_amount,
+        )?;
+
+        if penalty_to_stakers {
+            let received = transfer_from_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.principal_vault,
+                &mut ctx.accounts.reward_vault,
+                &ctx.accounts.stake_mint,
+                &ctx.accounts.staking_state,
+                penalty,
+            )?;
+
+            let staking_state = &mut ctx.accounts.staking_state;
+            staking_state.distribute_to_stakers(received)?;
+            staking_state.reward_reserve = staking_state.reward_reserve
+                .checked_add(received)
+                .ok_or(ProtocolError::Overflow)?;
+        } else {
+            let received = transfer_from_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.principal_vault,
+                &mut ctx.accounts.treasury_token_account,
+                &ctx.accounts.stake_mint,
+                &ctx.accounts.staking_state,
+                penalty,
+            )?;
+            ctx.accounts.treasury_ledger.record_income(TreasuryIncome::Penalty, received)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+        require!(amount > 0, ProtocolError::InvalidFundingAmount);
+
+        let received = transfer_to_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.funder_token_account,
+            &mut ctx.accounts.reward_vault,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.funder,
+            amount,
+        )?;
+
+        let staking_state = &mut ctx.accounts.staking_state;
+        staking_state.reward_reserve = staking_state.reward_reserve
+            .checked_add(received)
+            .ok_or(ProtocolError::Overflow)?;
+
+        emit!(RewardsFundedEvent {
+            pool: staking_state.key(),
+            funder: ctx.accounts.funder.key(),
+            amount: received,
+            reward_reserve: staking_state.reward_reserve,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn reward_runway(ctx: Context<ViewStakingState>) -> Result<RewardRunway> {
+        ctx.accounts.staking_state.reward_runway()
+    }
+
+    pub fn enable_liquid_staking(ctx: Context<EnableLiquidStaking>) -> Result<()> {
+        let staking_state = &mut ctx.accounts.staking_state;
+        require!(!staking_state.liquid_enabled(), ProtocolError::LiquidStakingEnabled);
+
+        let current_time = Clock::get()?.unix_timestamp;
+        staking_state.receipt_mint = ctx.accounts.receipt_mint.key();
+        staking_state.liquid_reward_per_token_paid = staking_state.update_reward_index(current_time)?;
+
+        emit!(LiquidStakingEnabledEvent {
+            pool: staking_state.key(),
+            receipt_mint: staking_state.receipt_mint,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn stake_liquid(ctx: Context<StakeLiquid>, amount: u64) -> Result<()> {
+        require!(!ctx.accounts.staking_state.paused, ProtocolError::ProtocolPaused);
+        require!(
+            amount >= ctx.accounts.staking_state.min_stake &&
+            amount <= ctx.accounts.staking_state.max_stake,
+            ProtocolError::InvalidStakeAmount
+        );
+
+        let current_time = Clock::get()?.unix_timestamp;
+        compound_liquid_rewards(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_vault,
+            &mut ctx.accounts.principal_vault,
+            &mut ctx.accounts.treasury_token_account,
+            &ctx.accounts.stake_mint,
+            &mut ctx.accounts.staking_state,
+            &ctx.accounts.treasury_state,
+            &mut ctx.accounts.treasury_ledger,
+            current_time,
+        )?;
+
+        let received = transfer_to_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.user_token_account,
+            &mut ctx.accounts.principal_vault,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.user,
+            amount,
+        )?;
+
+        let receipts = ctx.accounts.staking_state
+            .receipts_for_deposit(received, ctx.accounts.receipt_mint.supply)?;
+        require!(receipts > 0, ProtocolError::InvalidStakeAmount);
+
+        ctx.accounts.staking_state.add_liquid_stake(received)?;
+
+        let staking_state = &ctx.accounts.staking_state;
+        let staking_state_seeds = &[
+            b"staking_state".as_ref(),
+            staking_state.stake_mint.as_ref(),
+            &[staking_state.bump]
+        ];
+        let signer = &[&staking_state_seeds[..]];
+
+        token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.receipt_mint.to_account_info(),
+                    to: ctx.accounts.user_receipt_account.to_account_info(),
+                    authority: staking_state.to_account_info(),
+                },
+                signer
+            ),
+            receipts
+        )?;
+
+        emit!(LiquidStakeEvent {
+            pool: staking_state.key(),
+            user: ctx.accounts.user.key(),
+            amount: received,
+            receipts,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn unstake_liquid(ctx: Context<UnstakeLiquid>, receipts: u64) -> Result<()> {
+        require!(
+            receipts > 0 && receipts <= ctx.accounts.user_receipt_account.amount,
+            ProtocolError::InvalidReceiptAmount
+        );
+
+        let current_time = Clock::get()?.unix_timestamp;
+        compound_liquid_rewards(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_vault,
+            &mut ctx.accounts.principal_vault,
+            &mut ctx.accounts.treasury_token_account,
+            &ctx.accounts.stake_mint,
+            &mut ctx.accounts.staking_state,
+            &ctx.accounts.treasury_state,
+            &mut ctx.accounts.treasury_ledger,
+            current_time,
+        )?;
+
+        let amount = ctx.accounts.staking_state
+            .underlying_for_receipts(receipts, ctx.accounts.receipt_mint.supply)?;
+        require!(amount > 0, ProtocolError::InvalidReceiptAmount);
+
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.receipt_mint.to_account_info(),
+                    from: ctx.accounts.user_receipt_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            receipts
+        )?;
+
+        let staking_state = &mut ctx.accounts.staking_state;
+        staking_state.remove_liquid_stake(amount)?;
+
+        // Redeemed receipts go through the same unbonding period as a regular unstake.
+        let user_info = &mut ctx.accounts.user_stake_info;
+        user_info.owner = ctx.accounts.user.key();
+        user_info.pool = staking_state.key();
+        let release_time = current_time
+            .checked_add(staking_state.unbonding_period)
+            .ok_or(ProtocolError::Overflow)?;
+        user_info.start_unbonding(amount, release_time)?;
+        staking_state.add_unbonding(amount)?;
+
+        emit!(LiquidUnstakeEvent {
+            pool: staking_state.key(),
+            user: ctx.accounts.user.key(),
+            receipts,
+            amount,
+            release_time,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn liquid_exchange_rate(ctx: Context<ViewLiquidRate>) -> Result<LiquidExchangeRate> {
+        ctx.accounts.staking_state.liquid_exchange_rate(
+            Clock::get()?.unix_timestamp,
+            ctx.accounts.receipt_mint.supply,
+            &ctx.accounts.treasury_state,
+        )
+    }
+
+    pub fn compound_rewards(ctx: Context<CompoundRewards>) -> Result<()> {
+        require!(!ctx.accounts.staking_state.paused, ProtocolError::ProtocolPaused);
+        require!(
+            ctx.accounts.staking_state.reward_reserve > 0,
+            ProtocolError::RewardReserveEmpty
+        );
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let outcome = compound_position(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_vault,
+            &mut ctx.accounts.principal_vault,
+            &ctx.accounts.stake_mint,
+            &mut ctx.accounts.staking_state,
+            &ctx.accounts.treasury_state,
+            &mut ctx.accounts.user_stake_info,
+            &mut ctx.accounts.position,
+            false,
+            current_time,
+        )?;
+        require!(outcome.compounded > 0, ProtocolError::NoRewardsAvailable);
+
+        pay_protocol_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_vault,
+            &mut ctx.accounts.treasury_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            &mut ctx.accounts.treasury_ledger,
+            outcome.fee,
+        )?;
+
+        emit!(RewardsCompoundedEvent {
+            pool: ctx.accounts.staking_state.key(),
+            owner: ctx.accounts.user.key(),
+            position: ctx.accounts.position.index,
+            gross: outcome.gross,
+            fee: outcome.fee,
+            amount: outcome.compounded,
+            new_amount: ctx.accounts.position.amount,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_auto_compound(ctx: Context<SetAutoCompound>, enabled: bool) -> Result<()> {
+        ctx.accounts.position.auto_compound = enabled;
+        Ok(())
+    }
+
+    // Remaining accounts are (position, user_stake_info) pairs. Positions that have not
+    // opted in or have nothing to compound are skipped rather than failing the batch.
+    pub fn crank_compound<'info>(ctx: Context<'_, '_, 'info, 'info, CrankCompound<'info>>) -> Result<()> {
+        require!(!ctx.accounts.staking_state.paused, ProtocolError::ProtocolPaused);
+
+        let pairs = ctx.remaining_accounts.chunks_exact(2);
+        require!(
+            pairs.remainder().is_empty()
+                && (1..=MAX_COMPOUND_BATCH).contains(&pairs.len()),
+            ProtocolError::InvalidCompoundBatch
+        );
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let tip_due = ctx.accounts.keeper_state.record_run(KeeperTask::Compound, current_time)?;
+        let pool = ctx.accounts.staking_state.key();
+        let mut positions = 0u64;
+        let mut total_compounded = 0u64;
+        let mut total_tip = 0u64;
+        let mut total_fee = 0u64;
+
+        for pair in pairs {
+            let mut position = Account::<StakePosition>::try_from(&pair[0])?;
+            let mut user_info = Account::<UserStakeInfo>::try_from(&pair[1])?;
+            require!(
+                position.pool == pool && user_info.pool == pool,
+                ProtocolError::InvalidPool
+            );
+            require!(user_info.owner == position.owner, ProtocolError::InvalidCompoundBatch);
 
-        treasury_state.authority = ctx.accounts.authority.key();
-        treasury_state.withdrawal_limit = config.withdrawal_limit;
-        treasury_state.required_signatures = 3;
-        treasury_state.total_balance = 0;
+            if !position.auto_compound {
+                continue;
+            }
 
-        governance_state.current_apy = config.base_apy;
-        governance_state.voting_period = config.voting_period;
-        governance_state.required_quorum = 100;
-        governance_state.proposal_count = 0;
+            let outcome = compound_position(
+                &ctx.accounts.token_program,
+                &ctx.accounts.reward_vault,
+                &mut ctx.accounts.principal_vault,
+                &ctx.accounts.stake_mint,
+                &mut ctx.accounts.staking_state,
+                &ctx.accounts.treasury_state,
+                &mut user_info,
+                &mut position,
+                true,
+                current_time,
+            )?;
+            position.exit(&crate::ID)?;
+            user_info.exit(&crate::ID)?;
+
+            total_tip = total_tip
+                .checked_add(outcome.tip)
+                .ok_or(ProtocolError::Overflow)?;
+            total_fee = total_fee
+                .checked_add(outcome.fee)
+                .ok_or(ProtocolError::Overflow)?;
+            let compounded = outcome.compounded;
+            if compounded == 0 {
+                continue;
+            }
+
+            emit!(RewardsCompoundedEvent {
+                pool,
+                owner: position.owner,
+                position: position.index,
+                gross: outcome.gross,
+                fee: outcome.fee,
+                amount: compounded,
+                new_amount: position.amount,
+                timestamp: current_time,
+            });
+
+            positions += 1;
+            total_compounded = total_compounded
+                .checked_add(compounded)
+                .ok_or(ProtocolError::Overflow)?;
+        }
+        require!(positions > 0, ProtocolError::NothingToProcess);
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_vault,
+            &mut ctx.accounts.keeper_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            total_tip,
+        )?;
+        pay_protocol_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_vault,
+            &mut ctx.accounts.treasury_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            &mut ctx.accounts.treasury_ledger,
+            total_fee,
+        )?;
+
+        emit!(CompoundCrankEvent {
+            pool,
+            keeper: ctx.accounts.keeper.key(),
+            positions,
+            compounded: total_compounded,
+            protocol_fee: total_fee,
+            keeper_tip: total_tip,
+            timestamp: current_time,
+        });
+
+        let tip = pay_keeper_tip(
+            &ctx.accounts.token_program,
+            &ctx.accounts.keeper_budget,
+            &mut ctx.accounts.keeper_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            &mut ctx.accounts.keeper_state,
+            tip_due,
+        )?;
+
+        emit!(KeeperCrankEvent {
+            pool: ctx.accounts.staking_state.key(),
+            keeper: ctx.accounts.keeper.key(),
+            task: KeeperTask::Compound,
+            tip,
+            timestamp: current_time,
+        });
 
         Ok(())
     }
 
-    pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
-        require!(!ctx.accounts.staking_state.paused, ProtocolError::ProtocolPaused);
+    pub fn update_keeper_config(ctx: Context<UpdateKeeperConfig>, config: KeeperConfig) -> Result<()> {
+        ctx.accounts.keeper_state.apply_config(&config)
+    }
+
+    pub fn fund_keeper_budget(ctx: Context<FundKeeperBudget>, amount: u64) -> Result<()> {
+        require!(amount > 0, ProtocolError::InvalidFundingAmount);
+
+        let received = transfer_to_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.funder_token_account,
+            &mut ctx.accounts.keeper_budget,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.funder,
+            amount,
+        )?;
+
+        emit!(KeeperBudgetFundedEvent {
+            pool: ctx.accounts.staking_state.key(),
+            funder: ctx.accounts.funder.key(),
+            amount: received,
+            budget: ctx.accounts.keeper_budget.amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn crank_reward_index(ctx: Context<CrankRewardIndex>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let tip_due = ctx.accounts.keeper_state.record_run(KeeperTask::RewardIndex, current_time)?;
+        ctx.accounts.staking_state.update_reward_index(current_time)?;
+
+        let tip = pay_keeper_tip(
+            &ctx.accounts.token_program,
+            &ctx.accounts.keeper_budget,
+            &mut ctx.accounts.keeper_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            &mut ctx.accounts.keeper_state,
+            tip_due,
+        )?;
+
+        emit!(KeeperCrankEvent {
+            pool: ctx.accounts.staking_state.key(),
+            keeper: ctx.accounts.keeper.key(),
+            task: KeeperTask::RewardIndex,
+            tip,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn crank_reward_topup(ctx: Context<CrankRewardTopUp>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let tip_due = ctx.accounts.keeper_state.record_run(KeeperTask::RewardTopUp, current_time)?;
+
         require!(
-            amount >= ctx.accounts.staking_state.min_stake && 
-            amount <= ctx.accounts.staking_state.max_stake,
-            ProtocolError::InvalidStakeAmount
+            ctx.accounts.staking_state.reward_reserve < ctx.accounts.keeper_state.reward_topup_threshold,
+            ProtocolError::RewardReserveHealthy
         );
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.protocol_token_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts
-            ),
-            amount
+        let unreserved = ctx.accounts.treasury_token_account.amount
+            .saturating_sub(ctx.accounts.treasury_ledger.bond_liabilities);
+        let amount = ctx.accounts.keeper_state.reward_topup_amount
+            .min(ctx.accounts.treasury_ledger.withdrawal_headroom(current_time)?.available)
+            .min(unreserved);
+        require!(amount > 0, ProtocolError::InvalidFundingAmount);
+        ctx.accounts.treasury_ledger.record_withdrawal(amount, current_time)?;
+
+        let received = transfer_from_treasury(
+            &ctx.accounts.token_program,
+            &ctx.accounts.treasury_token_account,
+            &mut ctx.accounts.reward_vault,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.treasury_state,
+            ctx.bumps.treasury_state,
+            amount,
         )?;
+        ctx.accounts.treasury_ledger.record_spend(amount);
 
-        let user_info = &mut ctx.accounts.user_stake_info;
-        user_info.owner = ctx.accounts.user.key();
-        user_info.staked_amount = user_info.staked_amount
-            .checked_add(amount)
+        let staking_state = &mut ctx.accounts.staking_state;
+        staking_state.reward_reserve = staking_state.reward_reserve
+            .checked_add(received)
             .ok_or(ProtocolError::Overflow)?;
-        user_info.last_stake_timestamp = Clock::get()?.unix_timestamp;
 
-        ctx.accounts.staking_state.total_staked = ctx.accounts.staking_state.total_staked
-            .checked_add(amount)
-            .ok_or(ProtocolError::Overflow)?;
+        emit!(RewardsFundedEvent {
+            pool: staking_state.key(),
+            funder: ctx.accounts.treasury_state.key(),
+            amount: received,
+            reward_reserve: staking_state.reward_reserve,
+            timestamp: current_time,
+        });
+
+        let tip = pay_keeper_tip(
+            &ctx.accounts.token_program,
+            &ctx.accounts.keeper_budget,
+            &mut ctx.accounts.keeper_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            &mut ctx.accounts.keeper_state,
+            tip_due,
+        )?;
+
+        emit!(KeeperCrankEvent {
+            pool: ctx.accounts.staking_state.key(),
+            keeper: ctx.accounts.keeper.key(),
+            task: KeeperTask::RewardTopUp,
+            tip,
+            timestamp: current_time,
+        });
 
         Ok(())
     }
 
-    pub fn unstake(ctx: Context<Unstake>, amount: u64) -> Result<()> {
-        let user_info = &mut ctx.accounts.user_stake_info;
-        
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        let current_time = Clock::get()?.unix_timestamp;
+        let reward_per_token = ctx.accounts.staking_state.update_reward_index(current_time)?;
+        let loyalty_bonus = ctx.accounts.staking_state.loyalty_bonus_active();
+
+        let pending_rewards = position.settle_rewards(current_time, reward_per_token, loyalty_bonus)?;
+
+        require!(pending_rewards > 0, ProtocolError::NoRewardsAvailable);
         require!(
-            amount <= user_info.staked_amount,
-            ProtocolError::InvalidUnstakeAmount
+            ctx.accounts.staking_state.reward_reserve > 0,
+            ProtocolError::RewardReserveEmpty
         );
 
-        let current_time = Clock::get()?.unix_timestamp;
-        let rewards = user_info.calculate_rewards(
-            current_time,
-            ctx.accounts.staking_state.current_apy,
+        let rewards = ctx.accounts.staking_state.release_rewards(pending_rewards)?;
+        let fee = ctx.accounts.treasury_state.protocol_fee(rewards)?;
+        let net = rewards.checked_sub(fee).ok_or(ProtocolError::Overflow)?;
+
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_vault,
+            &mut ctx.accounts.user_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            net,
+        )?;
+        pay_protocol_fee(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_vault,
+            &mut ctx.accounts.treasury_token_account,
+            &ctx.accounts.stake_mint,
+            &ctx.accounts.staking_state,
+            &mut ctx.accounts.treasury_ledger,
+            fee,
         )?;
 
-        let total_transfer = amount.checked_add(rewards)
-            .ok_or(ProtocolError::Overflow)?;
+        position.record_reward_payout(rewards)?;
+        position.start_time = current_time;
 
-            let staking_state_seeds = &[
-                b"staking_state".as_ref(),
-                &[ctx.bumps.staking_state]
-            ];
-        let signer = &[&staking_state_seeds[..]];
+        emit!(RewardsClaimedEvent {
+            pool: ctx.accounts.staking_state.key(),
+            owner: position.owner,
+            position: position.index,
+            gross: rewards,
+            fee,
+            net,
+            timestamp: current_time,
+        });
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.protocol_token_account.to_account_info(),
-            to: ctx.accounts.user_token_account.to_account_info(),
-            authority: ctx.accounts.staking_state.to_account_info(),
-        };
+        Ok(())
+    }
 
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts,
-                signer
-            ),
-            total_transfer
+    pub fn create_price_feed(ctx: Context<CreatePriceFeed>, oracle: Pubkey, expo: i32) -> Result<()> {
+        let price_feed = &mut ctx.accounts.price_feed;
+        price_feed.authority = oracle;
+        price_feed.mint = ctx.accounts.mint.key();
+        price_feed.price = 0;
+        price_feed.conf = 0;
+        price_feed.expo = expo;
+        price_feed.publish_time = 0;
+        price_feed.bump = ctx.bumps.price_feed;
+
+        Ok(())
+    }
+
+    pub fn update_price_feed(ctx: Context<UpdatePriceFeed>, price: i64, conf: u64) -> Result<()> {
+        require!(price > 0, ProtocolError::InvalidPriceFeed);
+
+        let price_feed = &mut ctx.accounts.price_feed;
+        price_feed.price = price;
+        price_feed.conf = conf;
+        price_feed.publish_time = Clock::get()?.unix_timestamp;
+
+        emit!(PriceFeedUpdatedEvent {
+            feed: price_feed.key(),
+            mint: price_feed.mint,
+            price,
+            conf,
+            publish_time: price_feed.publish_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_bond_market(ctx: Context<CreateBondMarket>, config: BondMarketConfig) -> Result<()> {
+        let market = &mut ctx.accounts.bond_market;
+        let current_time = Clock::get()?.unix_timestamp;
+
+        market.quote_mint = ctx.accounts.quote_mint.key();
+        market.payout_mint = ctx.accounts.payout_mint.key();
+        market.apply_config(&config)?;
+        market.sold = 0;
+        market.total_debt = 0;
+        market.last_decay = current_time;
+        market.outstanding_payout = 0;
+        market.total_quote_received = 0;
+        market.adjustment = BondAdjustment::default();
+        market.price_feed = ctx.accounts.price_feed
+            .as_ref()
+            .map_or(Pubkey::default(), |price_feed| price_feed.key());
+        market.bump = ctx.bumps.bond_market;
+
+        open_treasury_ledger(
+            &mut ctx.accounts.treasury_quote_ledger,
+            &ctx.accounts.treasury_quote_vault,
+            ctx.bumps.treasury_quote_ledger,
+        );
+        open_treasury_ledger(
+            &mut ctx.accounts.treasury_payout_ledger,
+            &ctx.accounts.treasury_payout_vault,
+            ctx.bumps.treasury_payout_ledger,
+        );
+
+        emit!(BondMarketCreatedEvent {
+            market: market.key(),
+            quote_mint: market.quote_mint,
+            payout_mint: market.payout_mint,
+            control_variable: market.control_variable,
+            capacity: market.capacity,
+            vesting_term: market.vesting_term,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_bond_adjustment(
+        ctx: Context<UpdateBondMarket>,
+        add: bool,
+        rate: u64,
+        target: u64,
+        buffer: i64,
+    ) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        ctx.accounts.bond_market.set_adjustment(add, rate, target, buffer, current_time)
+    }
+
+    pub fn create_bond(ctx: Context<CreateBond>, amount: u64, max_price: u64) -> Result<()> {
+        require!(amount > 0, ProtocolError::InvalidBondAmount);
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let market = &mut ctx.accounts.bond_market;
+        market.decay_debt(current_time)?;
+        if market.adjust(current_time)? {
+            emit!(ControlVariableAdjustedEvent {
+                market: market.key(),
+                control_variable: market.control_variable,
+                target: market.adjustment.target,
+                timestamp: current_time,
+            });
+        }
+
+        let price = market.bond_price(ctx.accounts.payout_mint.supply)?;
+        require!(price <= max_price, ProtocolError::BondPriceExceeded);
+
+        let received = transfer_to_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.user_quote_account,
+            &mut ctx.accounts.treasury_quote_account,
+            &ctx.accounts.quote_mint,
+            &ctx.accounts.user,
+            amount,
         )?;
+        ctx.accounts.treasury_quote_ledger.record_income(TreasuryIncome::BondProceeds, received)?;
 
-        user_info.staked_amount = user_info.staked_amount
-            .checked_sub(amount)
-            .ok_or(ProtocolError::Overflow)?;
-        user_info.rewards_claimed = user_info.rewards_claimed
-            .checked_add(rewards)
-            .ok_or(ProtocolError::Overflow)?;
+        // Without a feed one whole quote token is valued at one whole payout token.
+        let market = &mut ctx.accounts.bond_market;
+        let value = if market.has_price_feed() {
+            let price_feed = ctx.accounts.price_feed
+                .as_ref()
+                .ok_or(ProtocolError::InvalidPriceFeed)?;
+            let feed_price = price_feed.validate(
+                current_time,
+                market.max_price_age,
+                market.max_confidence_bps,
+            )?;
+            price_feed.value_of(
+                received,
+                feed_price,
+                ctx.accounts.quote_mint.decimals,
+                ctx.accounts.payout_mint.decimals,
+            )?
+        } else {
+            rescale(
+                received as u128,
+                0,
+                ctx.accounts.quote_mint.decimals,
+                ctx.accounts.payout_mint.decimals,
+            )?
+        };
 
-        ctx.accounts.staking_state.total_staked = ctx.accounts.staking_state.total_staked
-            .checked_sub(amount)
+        let payout = market.payout_for(value, price)?;
+        require!(payout > 0, ProtocolError::InvalidBondAmount);
+        market.record_bond(received, payout)?;
+        ctx.accounts.treasury_payout_ledger
+            .reserve_bond_payout(ctx.accounts.treasury_payout_account.amount, payout)?;
+
+        let user_bond_info = &mut ctx.accounts.user_bond_info;
+        user_bond_info.owner = ctx.accounts.user.key();
+        user_bond_info.market = market.key();
+
+        let bond = &mut ctx.accounts.bond;
+        bond.owner = ctx.accounts.user.key();
+        bond.market = market.key();
+        bond.index = user_bond_info.next_bond_index()?;
+        bond.deposit = received;
+        bond.payout = payout;
+        bond.price = price;
+        bond.created_at = current_time;
+        bond.maturity = current_time
+            .checked_add(market.vesting_term)
             .ok_or(ProtocolError::Overflow)?;
+        bond.vested_paid = 0;
+        bond.bump = ctx.bumps.bond;
+
+        emit!(BondCreatedEvent {
+            market: market.key(),
+            user: bond.owner,
+            bond: bond.index,
+            deposit: received,
+            value,
+            payout,
+            price,
+            maturity: bond.maturity,
+            timestamp: current_time,
+        });
 
         Ok(())
     }
 
-    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
-        let user_info = &mut ctx.accounts.user_stake_info;
+    // Pays whatever has vested since the last call and closes the bond once the
+    // full payout has been redeemed.
+    pub fn redeem_bond(ctx: Context<RedeemBond>) -> Result<()> {
+        let bond = &mut ctx.accounts.bond;
         let current_time = Clock::get()?.unix_timestamp;
-        
-        let rewards = user_info.calculate_rewards(
-            current_time,
-            ctx.accounts.staking_state.current_apy,
+
+        let amount = bond.redeemable(current_time)?;
+        require!(amount > 0, ProtocolError::NothingToRedeem);
+
+        transfer_from_treasury(
+            &ctx.accounts.token_program,
+            &ctx.accounts.treasury_payout_account,
+            &mut ctx.accounts.user_payout_account,
+            &ctx.accounts.payout_mint,
+            &ctx.accounts.treasury_state,
+            ctx.bumps.treasury_state,
+            amount,
         )?;
+        ctx.accounts.treasury_payout_ledger.record_spend(amount);
+        ctx.accounts.treasury_payout_ledger.release_bond_payout(amount)?;
 
-        require!(rewards > 0, ProtocolError::NoRewardsAvailable);
+        bond.record_redemption(amount)?;
+        ctx.accounts.bond_market.record_payout(amount)?;
 
-        let staking_state_seeds = &[
-            b"staking_state".as_ref(),
-            &[ctx.bumps.staking_state]
-        ];
-        let signer = &[&staking_state_seeds[..]];
+        emit!(BondRedeemedEvent {
+            market: ctx.accounts.bond_market.key(),
+            user: bond.owner,
+            bond: bond.index,
+            amount,
+            vested_paid: bond.vested_paid,
+            remaining: bond.payout.saturating_sub(bond.vested_paid),
+            timestamp: current_time,
+        });
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.protocol_token_account.to_account_info(),
-            to: ctx.accounts.user_token_account.to_account_info(),
-            authority: ctx.accounts.staking_state.to_account_info(),
-        };
+        if bond.fully_redeemed() {
+            bond.close(ctx.accounts.user.to_account_info())?;
+        }
 
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts,
-                signer
-            ),
-            rewards
+        Ok(())
+    }
+
+    pub fn deposit_to_treasury(ctx: Context<DepositToTreasury>, amount: u64) -> Result<()> {
+        require!(amount > 0, ProtocolError::InvalidFundingAmount);
+
+        let received = transfer_to_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.depositor_token_account,
+            &mut ctx.accounts.treasury_vault,
+            &ctx.accounts.mint,
+            &ctx.accounts.depositor,
+            amount,
         )?;
 
-        user_info.rewards_claimed = user_info.rewards_claimed
-            .checked_add(rewards)
-            .ok_or(ProtocolError::Overflow)?;
-        user_info.last_stake_timestamp = current_time;
+        let treasury_ledger = &mut ctx.accounts.treasury_ledger;
+        treasury_ledger.record_income(TreasuryIncome::Deposit, received)?;
+
+        emit!(TreasuryDepositEvent {
+            depositor: ctx.accounts.depositor.key(),
+            mint: treasury_ledger.mint,
+            amount: received,
+            total_balance: treasury_ledger.total_balance,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Permissionless: anyone can pull the recorded balance back in line with the
+    // vault after direct transfers or other untracked movements.
+    pub fn sync_treasury(ctx: Context<SyncTreasury>) -> Result<()> {
+        let treasury_ledger = &mut ctx.accounts.treasury_ledger;
+        let recorded_balance = treasury_ledger.total_balance;
+        let actual_balance = ctx.accounts.treasury_vault.amount;
+        let difference = treasury_ledger.sync_balance(actual_balance);
+
+        emit!(TreasurySyncedEvent {
+            caller: ctx.accounts.caller.key(),
+            mint: treasury_ledger.mint,
+            recorded_balance,
+            actual_balance,
+            difference,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdrawal_headroom(ctx: Context<ViewTreasuryLedger>) -> Result<WithdrawalHeadroom> {
+        ctx.accounts.treasury_ledger.withdrawal_headroom(Clock::get()?.unix_timestamp)
+    }
+
+    pub fn propose_treasury_transaction(
+        ctx: Context<ProposeTreasuryTransaction>,
+        action: TreasuryAction,
+    ) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let treasury_state = &mut ctx.accounts.treasury_state;
+        let signer_index = treasury_state.signer_index(&ctx.accounts.proposer.key())?;
+
+        match &action {
+            TreasuryAction::Transfer { amount, .. } => {
+                require!(*amount > 0, ProtocolError::InvalidWithdrawalAmount);
+            }
+            TreasuryAction::SetSigners { signers, threshold } => {
+                require!(
+                    !signers.is_empty()
+                        && signers.len() <= MAX_TREASURY_SIGNERS
+                        && *threshold > 0
+                        && *threshold as usize <= signers.len(),
+                    ProtocolError::InvalidTreasurySigners
+                );
+            }
+            TreasuryAction::ChangeThreshold { threshold } => {
+                require!(
+                    *threshold > 0 && *threshold <= treasury_state.signer_count,
+                    ProtocolError::InvalidTreasurySigners
+                );
+            }
+            // The amount is checked against the pool's ledger limit on execution.
+            TreasuryAction::SetRewardTopUp { .. } => {}
+        }
+
+        let index = treasury_state.next_transaction_index()?;
+
+        let transaction = &mut ctx.accounts.transaction;
+        transaction.treasury = treasury_state.key();
+        transaction.proposer = ctx.accounts.proposer.key();
+        transaction.index = index;
+        transaction.action = action.clone();
+        transaction.approvals = [false; MAX_TREASURY_SIGNERS];
+        transaction.owner_set_seqno = treasury_state.owner_set_seqno;
+        transaction.created_at = current_time;
+        transaction.executed = false;
+        transaction.bump = ctx.bumps.transaction;
+        transaction.approve(signer_index)?;
+
+        emit!(TreasuryTransactionProposedEvent {
+            transaction: transaction.key(),
+            proposer: transaction.proposer,
+            index,
+            action,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_treasury_transaction(ctx: Context<ApproveTreasuryTransaction>) -> Result<()> {
+        let treasury_state = &ctx.accounts.treasury_state;
+        let transaction = &mut ctx.accounts.transaction;
+        require!(!transaction.is_stale(treasury_state), ProtocolError::StaleTransaction);
+
+        let signer_index = treasury_state.signer_index(&ctx.accounts.signer.key())?;
+        let approvals = transaction.approve(signer_index)?;
+
+        emit!(TreasuryTransactionApprovedEvent {
+            transaction: transaction.key(),
+            signer: ctx.accounts.signer.key(),
+            approvals,
+            threshold: treasury_state.required_signatures,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_treasury_transaction(ctx: Context<ExecuteTreasuryTransaction>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let treasury_state = &ctx.accounts.treasury_state;
+        let transaction = &ctx.accounts.transaction;
+
+        treasury_state.signer_index(&ctx.accounts.executor.key())?;
+        require!(!transaction.executed, ProtocolError::TransactionAlreadyExecuted);
+        require!(!transaction.is_stale(treasury_state), ProtocolError::StaleTransaction);
+        require!(
+            transaction.approval_count() >= treasury_state.required_signatures,
+            ProtocolError::InsufficientSignatures
+        );
+
+        let action = transaction.action.clone();
+        match &action {
+            TreasuryAction::Transfer { mint, destination, amount } => {
+                let (
+                    Some(treasury_ledger),
+                    Some(treasury_token_account),
+                    Some(destination_account),
+                    Some(mint_account),
+                    Some(token_program),
+                ) = (
+                    ctx.accounts.treasury_ledger.as_mut(),
+                    ctx.accounts.treasury_token_account.as_ref(),
+                    ctx.accounts.destination.as_mut(),
+                    ctx.accounts.mint.as_ref(),
+                    ctx.accounts.token_program.as_ref(),
+                ) else {
+                    return err!(ProtocolError::InvalidTreasuryAccount);
+                };
+                require!(
+                    mint_account.key() == *mint
+                        && treasury_ledger.mint == *mint
+                        && treasury_token_account.key() == treasury_ledger.vault
+                        && destination_account.key() == *destination,
+                    ProtocolError::InvalidTreasuryAccount
+                );
+                treasury_ledger.require_unreserved(treasury_token_account.amount, *amount)?;
+
+                transfer_from_treasury(
+                    token_program,
+                    treasury_token_account,
+                    destination_account,
+                    mint_account,
+                    treasury_state,
+                    ctx.bumps.treasury_state,
+                    *amount,
+                )?;
+                treasury_ledger.record_spend(*amount);
+                treasury_ledger.record_withdrawal(*amount, current_time)?;
+            }
+            TreasuryAction::SetSigners { signers, threshold } => {
+                let treasury_state = &mut ctx.accounts.treasury_state;
+                treasury_state.set_signers(signers, *threshold)?;
+
+                emit!(TreasurySignersUpdatedEvent {
+                    signers: signers.clone(),
+                    threshold: *threshold,
+                    owner_set_seqno: treasury_state.owner_set_seqno,
+                    timestamp: current_time,
+                });
+            }
+            TreasuryAction::ChangeThreshold { threshold } => {
+                let treasury_state = &mut ctx.accounts.treasury_state;
+                let signers = treasury_state.active_signers().to_vec();
+                treasury_state.set_signers(&signers, *threshold)?;
+
+                emit!(TreasurySignersUpdatedEvent {
+                    signers,
+                    threshold: *threshold,
+                    owner_set_seqno: treasury_state.owner_set_seqno,
+                    timestamp: current_time,
+                });
+            }
+            TreasuryAction::SetRewardTopUp { pool, amount, threshold } => {
+                let treasury_ledger = ctx.accounts.treasury_ledger
+                    .as_ref()
+                    .filter(|treasury_ledger| {
+                        Pubkey::find_program_address(
+                            &[b"staking_state", treasury_ledger.mint.as_ref()],
+                            &crate::ID,
+                        ).0 == *pool
+                    })
+                    .ok_or(ProtocolError::InvalidTreasuryAccount)?;
+                let keeper_state = ctx.accounts.keeper_state
+                    .as_mut()
+                    .filter(|keeper_state| keeper_state.pool == *pool)
+                    .ok_or(ProtocolError::InvalidPool)?;
+                keeper_state.set_reward_topup(*amount, *threshold, treasury_ledger.withdrawal_limit)?;
+            }
+        }
+
+        ctx.accounts.transaction.executed = true;
+
+        emit!(TreasuryTransactionExecutedEvent {
+            transaction: ctx.accounts.transaction.key(),
+            executor: ctx.accounts.executor.key(),
+            action,
+            timestamp: current_time,
+        });
+
+        Ok(())
+    }
+
+    // The proposer can withdraw a pending transaction; any signer can clear one left
+    // stale by a signer-set change.
+    pub fn cancel_treasury_transaction(ctx: Context<CancelTreasuryTransaction>) -> Result<()> {
+        let treasury_state = &ctx.accounts.treasury_state;
+        let transaction = &ctx.accounts.transaction;
+        let signer = ctx.accounts.signer.key();
+
+        require!(!transaction.executed, ProtocolError::TransactionAlreadyExecuted);
+        if transaction.is_stale(treasury_state) {
+            treasury_state.signer_index(&signer)?;
+        } else {
+            require!(signer == transaction.proposer, ProtocolError::InvalidAuthority);
+        }
+
+        emit!(TreasuryTransactionCancelledEvent {
+            transaction: transaction.key(),
+            signer,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
         Ok(())
     }
@@ -241,12 +1887,26 @@ pub mod death_protocol {
         );
 
         match &proposal.data {
-            ProposalData::UpdateApy { new_apy } => {
-                ctx.accounts.staking_state.current_apy = *new_apy;
-                ctx.accounts.governance_state.current_apy = *new_apy;
+            ProposalData::UpdateApy { pool, new_apy } => {
+                require!(
+                    (MIN_APY..=MAX_APY).contains(new_apy),
+                    ProtocolError::InvalidAPY
+                );
+                let staking_state = proposal_pool(&mut ctx.accounts.staking_state, pool)?;
+                staking_state.update_reward_index(current_time)?;
+                staking_state.current_apy = *new_apy;
             },
-            ProposalData::UpdateWithdrawalLimit { new_limit } => {
-                ctx.accounts.treasury_state.withdrawal_limit = *new_limit;
+            ProposalData::UpdateWithdrawalLimit { mint, new_limit } => {
+                let treasury_ledger = proposal_ledger(&mut ctx.accounts.treasury_ledger, mint)?;
+                let period = treasury_ledger.withdrawal_period;
+                treasury_ledger.set_withdrawal_limit(*new_limit, period)?;
+
+                emit!(WithdrawalLimitUpdatedEvent {
+                    mint: *mint,
+                    withdrawal_limit: *new_limit,
+                    withdrawal_period: period,
+                    timestamp: current_time,
+                });
             },
             ProposalData::UpdateVotingPeriod { new_period } => {
                 ctx.accounts.governance_state.voting_period = *new_period;
@@ -254,10 +1914,502 @@ pub mod death_protocol {
             ProposalData::UpdateQuorum { new_quorum } => {
                 ctx.accounts.governance_state.required_quorum = *new_quorum;
             },
+            ProposalData::UpdateRewardMode { pool, mode, emission_rate, step_interval, step_percent } => {
+                proposal_pool(&mut ctx.accounts.staking_state, pool)?.set_reward_mode(
+                    *mode,
+                    *emission_rate,
+                    *step_interval,
+                    *step_percent,
+                    current_time,
+                )?;
+
+                emit!(RewardModeUpdatedEvent {
+                    pool: *pool,
+                    mode: *mode,
+                    emission_rate: *emission_rate,
+                    step_interval: *step_interval,
+                    step_percent: *step_percent,
+                    timestamp: current_time,
+                });
+            },
+            ProposalData::UpdateLockTier { pool, tier, duration, multiplier_bps } => {
+                // Existing positions keep the multiplier they locked in with.
+                let staking_state = proposal_pool(&mut ctx.accounts.staking_state, pool)?;
+                staking_state.set_lock_tier(*tier, *duration, *multiplier_bps)?;
+
+                emit!(LockTierUpdatedEvent {
+                    pool: *pool,
+                    tier: *tier,
+                    duration: *duration,
+                    multiplier_bps: *multiplier_bps,
+                    timestamp: current_time,
+                });
+            },
+            ProposalData::UpdateOutflowCap { new_cap, new_epoch_duration } => {
+                ctx.accounts.treasury_state.set_outflow_cap(*new_cap, *new_epoch_duration, current_time)?;
+
+                emit!(OutflowCapUpdatedEvent {
+                    epoch_outflow_cap: *new_cap,
+                    epoch_duration: *new_epoch_duration,
+                    timestamp: current_time,
+                });
+            },
+            ProposalData::UpdateWithdrawalPeriod { mint, new_period } => {
+                let treasury_ledger = proposal_ledger(&mut ctx.accounts.treasury_ledger, mint)?;
+                let limit = treasury_ledger.withdrawal_limit;
+                treasury_ledger.set_withdrawal_limit(limit, *new_period)?;
+
+                emit!(WithdrawalLimitUpdatedEvent {
+                    mint: *mint,
+                    withdrawal_limit: limit,
+                    withdrawal_period: *new_period,
+                    timestamp: current_time,
+                });
+            },
+            ProposalData::UpdateProtocolFee { new_fee_bps } => {
+                ctx.accounts.treasury_state.set_protocol_fee(*new_fee_bps)?;
+
+                emit!(ProtocolFeeUpdatedEvent {
+                    protocol_fee_bps: *new_fee_bps,
+                    timestamp: current_time,
+                });
+            },
         }
 
         proposal.executed = true;
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+fn transfer_to_vault<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    vault: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    authority: &Signer<'info>,
+    amount: u64,
+) -> Result<u64> {
+    let balance_before = vault.amount;
+
+    token_interface::transfer_checked(
+        CpiContext::new(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: from.to_account_info(),
+                mint: mint.to_account_info(),
+                to: vault.to_account_info(),
+                authority: authority.to_account_info(),
+            },
+        ),
+        amount,
+        mint.decimals
+    )?;
+
+    received_since(vault, balance_before)
+}
+
+fn transfer_from_vault<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    destination: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    staking_state: &Account<'info, StakingState>,
+    amount: u64,
+) -> Result<u64> {
+    if amount == 0 {
+        return Ok(0);
+    }
+
+    let balance_before = destination.amount;
+
+    let staking_state_seeds = &[
+        b"staking_state".as_ref(),
+        staking_state.stake_mint.as_ref(),
+        &[staking_state.bump]
+    ];
+    let signer = &[&staking_state_seeds[..]];
+
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: vault.to_account_info(),
+                mint: mint.to_account_info(),
+                to: destination.to_account_info(),
+                authority: staking_state.to_account_info(),
+            },
+            signer
+        ),
+        amount,
+        mint.decimals
+    )?;
+
+    received_since(destination, balance_before)
+}
+
+fn withdraw_from_position(
+    staking_state: &mut StakingState,
+    user_info: &mut UserStakeInfo,
+    position: &mut StakePosition,
+    amount: u64,
+    current_time: i64,
+) -> Result<()> {
+    require!(
+        amount > 0 && amount <= position.amount,
+        ProtocolError::InvalidUnstakeAmount
+    );
+    require!(!position.is_locked(current_time), ProtocolError::PositionLocked);
+
+    let reward_per_token = staking_state.update_reward_index(current_time)?;
+    let loyalty_bonus = staking_state.loyalty_bonus_active();
+    position.settle_rewards(current_time, reward_per_token, loyalty_bonus)?;
+    let weight_before = position.effective_stake()?;
+    position.amount = position.amount
+        .checked_sub(amount)
+        .ok_or(ProtocolError::Overflow)?;
+
+    user_info.staked_amount = user_info.staked_amount
+        .checked_sub(amount)
+        .ok_or(ProtocolError::Overflow)?;
+    staking_state.total_staked = staking_state.total_staked
+        .checked_sub(amount)
+        .ok_or(ProtocolError::Overflow)?;
+    staking_state.reweight_stake(weight_before, position.effective_stake()?)?;
+
+    Ok(())
+}
+
+fn enqueue_withdrawal(
+    withdrawal_state: &mut Account<WithdrawalState>,
+    request: &mut WithdrawalRequest,
+    owner: Pubkey,
+    position: u64,
+    amount: u64,
+    request_time: i64,
+    release_time: i64,
+) -> Result<()> {
+    request.queue = withdrawal_state.key();
+    request.owner = owner;
+    request.position = position;
+    request.index = withdrawal_state.enqueue(amount)?;
+    request.amount = amount;
+    request.request_time = request_time;
+    request.release_time = release_time;
+    request.cancelled = false;
+
+    Ok(())
+}
+
+fn init_position(
+    user_info: &mut UserStakeInfo,
+    position: &mut StakePosition,
+    owner: Pubkey,
+    pool: Pubkey,
+    lock_tier: u8,
+    tier: LockTier,
+    bump: u8,
+) -> Result<()> {
+    user_info.owner = owner;
+    user_info.pool = pool;
+
+    position.owner = owner;
+    position.pool = pool;
+    position.index = user_info.next_position_index()?;
+    position.amount = 0;
+    position.lock_end = 0;
+    position.apply_lock_tier(lock_tier, tier);
+    position.pending_rewards = 0;
+    position.rewards_claimed = 0;
+    position.bump = bump;
+
+    Ok(())
+}
+
+fn deposit_into_position(
+    staking_state: &mut StakingState,
+    user_info: &mut UserStakeInfo,
+    position: &mut StakePosition,
+    received: u64,
+    current_time: i64,
+) -> Result<()> {
+    let reward_per_token = staking_state.update_reward_index(current_time)?;
+    let loyalty_bonus = staking_state.loyalty_bonus_active();
+    position.settle_rewards(current_time, reward_per_token, loyalty_bonus)?;
+    let weight_before = position.effective_stake()?;
+    position.amount = position.amount
+        .checked_add(received)
+        .ok_or(ProtocolError::Overflow)?;
+    position.start_time = current_time;
+    position.extend_lock(current_time)?;
+
+    user_info.staked_amount = user_info.staked_amount
+        .checked_add(received)
+        .ok_or(ProtocolError::Overflow)?;
+    staking_state.total_staked = staking_state.total_staked
+        .checked_add(received)
+        .ok_or(ProtocolError::Overflow)?;
+    staking_state.reweight_stake(weight_before, position.effective_stake()?)?;
+
+    Ok(())
+}
+
+fn pay_keeper_tip<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    keeper_budget: &InterfaceAccount<'info, TokenAccount>,
+    keeper_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    staking_state: &Account<'info, StakingState>,
+    keeper_state: &mut KeeperState,
+    tip_due: bool,
+) -> Result<u64> {
+    // An empty budget never blocks maintenance; the keeper just goes unpaid.
+    let tip = if tip_due { keeper_state.tip_amount.min(keeper_budget.amount) } else { 0 };
+
+    transfer_from_vault(
+        token_program,
+        keeper_budget,
+        keeper_token_account,
+        mint,
+        staking_state,
+        tip,
+    )?;
+    keeper_state.record_tip(tip)?;
+
+    Ok(tip)
+}
+
+fn transfer_from_treasury<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    treasury_token_account: &InterfaceAccount<'info, TokenAccount>,
+    destination: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    treasury_state: &Account<'info, TreasuryState>,
+    treasury_bump: u8,
+    amount: u64,
+) -> Result<u64> {
+    if amount == 0 {
+        return Ok(0);
+    }
+
+    let balance_before = destination.amount;
+
+    let treasury_seeds = &[b"treasury_state".as_ref(), &[treasury_bump]];
+    let signer = &[&treasury_seeds[..]];
+
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: treasury_token_account.to_account_info(),
+                mint: mint.to_account_info(),
+                to: destination.to_account_info(),
+                authority: treasury_state.to_account_info(),
+            },
+            signer
+        ),
+        amount,
+        mint.decimals
+    )?;
+
+    received_since(destination, balance_before)
+}
+
+// Transfer-fee mints deliver less than the amount sent, so callers account for
+// what actually arrived.
+fn received_since<'info>(
+    account: &mut InterfaceAccount<'info, TokenAccount>,
+    balance_before: u64,
+) -> Result<u64> {
+    account.reload()?;
+    account.amount
+        .checked_sub(balance_before)
+        .ok_or(error!(ProtocolError::Overflow))
+}
+
+// Moves a position's settled rewards from the reward vault into principal. When
+// `with_tip` is set the keeper tip is held back from the compounded amount and left
+// in the reward vault for the caller to pay out.
+#[allow(clippy::too_many_arguments)]
+fn compound_position<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    reward_vault: &InterfaceAccount<'info, TokenAccount>,
+    principal_vault: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    staking_state: &mut Account<'info, StakingState>,
+    treasury_state: &TreasuryState,
+    user_info: &mut UserStakeInfo,
+    position: &mut StakePosition,
+    with_tip: bool,
+    current_time: i64,
+) -> Result<CompoundOutcome> {
+    let reward_per_token = staking_state.update_reward_index(current_time)?;
+    let loyalty_bonus = staking_state.loyalty_bonus_active();
+    let pending = position.settle_rewards(current_time, reward_per_token, loyalty_bonus)?;
+    let released = staking_state.release_rewards(pending)?;
+    if released == 0 {
+        return Ok(CompoundOutcome::default());
+    }
+    position.record_reward_payout(released)?;
+
+    let fee = treasury_state.protocol_fee(released)?;
+    let net = released.checked_sub(fee).ok_or(ProtocolError::Overflow)?;
+    let tip = if with_tip { staking_state.keeper_tip(net)? } else { 0 };
+    let to_stake = net.checked_sub(tip).ok_or(ProtocolError::Overflow)?;
+
+    let received = transfer_from_vault(
+        token_program,
+        reward_vault,
+        principal_vault,
+        mint,
+        staking_state,
+        to_stake,
+    )?;
+
+    let weight_before = position.effective_stake()?;
+    position.amount = position.amount
+        .checked_add(received)
+        .ok_or(ProtocolError::Overflow)?;
+    user_info.staked_amount = user_info.staked_amount
+        .checked_add(received)
+        .ok_or(ProtocolError::Overflow)?;
+    staking_state.total_staked = staking_state.total_staked
+        .checked_add(received)
+        .ok_or(ProtocolError::Overflow)?;
+    staking_state.reweight_stake(weight_before, position.effective_stake()?)?;
+
+    Ok(CompoundOutcome {
+        gross: released,
+        fee,
+        tip,
+        compounded: received,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn compound_liquid_rewards<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    reward_vault: &InterfaceAccount<'info, TokenAccount>,
+    principal_vault: &mut InterfaceAccount<'info, TokenAccount>,
+    treasury_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    staking_state: &mut Account<'info, StakingState>,
+    treasury_state: &TreasuryState,
+    treasury_ledger: &mut TreasuryLedger,
+    current_time: i64,
+) -> Result<()> {
+    let reward_per_token = staking_state.update_reward_index(current_time)?;
+    let pending = staking_state.accrue_liquid_rewards(reward_per_token)?;
+    let released = staking_state.release_rewards(pending)?;
+    if released == 0 {
+        return Ok(());
+    }
+
+    staking_state.liquid_pending_rewards = pending
+        .checked_sub(released)
+        .ok_or(ProtocolError::Overflow)?;
+
+    let fee = treasury_state.protocol_fee(released)?;
+    let net = released.checked_sub(fee).ok_or(ProtocolError::Overflow)?;
+    pay_protocol_fee(
+        token_program,
+        reward_vault,
+        treasury_token_account,
+        mint,
+        staking_state,
+        treasury_ledger,
+        fee,
+    )?;
+
+    let received = transfer_from_vault(
+        token_program,
+        reward_vault,
+        principal_vault,
+        mint,
+        staking_state,
+        net,
+    )?;
+
+    emit!(LiquidRewardsCompoundedEvent {
+        pool: staking_state.key(),
+        gross: released,
+        fee,
+        net: received,
+        timestamp: current_time,
+    });
+
+    staking_state.add_liquid_stake(received)
+}
+
+// Moves the protocol's cut of a reward payout from the reward vault to the treasury.
+fn pay_protocol_fee<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    reward_vault: &InterfaceAccount<'info, TokenAccount>,
+    treasury_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    staking_state: &Account<'info, StakingState>,
+    treasury_ledger: &mut TreasuryLedger,
+    fee: u64,
+) -> Result<()> {
+    let received = transfer_from_vault(
+        token_program,
+        reward_vault,
+        treasury_token_account,
+        mint,
+        staking_state,
+        fee,
+    )?;
+
+    treasury_ledger.record_income(TreasuryIncome::ProtocolFee, received)
+}
+
+// Ledgers are shared by every pool and bond market in the same mint, so opening
+// one that already exists only rewrites the same identity fields.
+fn open_treasury_ledger<'info>(
+    treasury_ledger: &mut TreasuryLedger,
+    treasury_vault: &InterfaceAccount<'info, TokenAccount>,
+    bump: u8,
+) {
+    treasury_ledger.mint = treasury_vault.mint;
+    treasury_ledger.vault = treasury_vault.key();
+    treasury_ledger.bump = bump;
+}
+
+// Returns the program that invoked us through CPI, or None for a top-level call.
+// The instructions sysvar only shows top-level instructions, so the caller is only
+// known when we run directly beneath one; deeper CPI chains are rejected.
+fn calling_program(instructions: &AccountInfo) -> Result<Option<Pubkey>> {
+    let stack_height = get_stack_height();
+    if stack_height == TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Ok(None);
+    }
+    require!(
+        stack_height == TRANSACTION_LEVEL_STACK_HEIGHT + 1,
+        ProtocolError::UnknownCallingProgram
+    );
+
+    let current_index = load_current_index_checked(instructions)?;
+    let instruction = load_instruction_at_checked(current_index as usize, instructions)?;
+
+    Ok(Some(instruction.program_id))
+}
+
+fn proposal_pool<'a, 'info>(
+    staking_state: &'a mut Option<Account<'info, StakingState>>,
+    pool: &Pubkey,
+) -> Result<&'a mut Account<'info, StakingState>> {
+    match staking_state {
+        Some(staking_state) if staking_state.key() == *pool => Ok(staking_state),
+        _ => err!(ProtocolError::InvalidPool),
+    }
+}
+
+fn proposal_ledger<'a, 'info>(
+    treasury_ledger: &'a mut Option<Account<'info, TreasuryLedger>>,
+    mint: &Pubkey,
+) -> Result<&'a mut Account<'info, TreasuryLedger>> {
+    match treasury_ledger {
+        Some(treasury_ledger) if treasury_ledger.mint == *mint => Ok(treasury_ledger),
+        _ => err!(ProtocolError::InvalidTreasuryAccount),
+    }
+}
@@ -1,73 +1,728 @@
 use anchor_lang::prelude::*;
+use crate::constants::*;
 use crate::error::ProtocolError;
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct ProtocolConfig {
+    pub treasury_signers: Vec<Pubkey>,
+    pub required_signatures: u8,
+    pub withdrawal_limit: u64,
+    pub withdrawal_period: i64,
+    pub epoch_outflow_cap: u64,
+    pub epoch_duration: i64,
+    pub protocol_fee_bps: u64,
+    pub voting_period: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PoolConfig {
     pub base_apy: u64, 
     pub min_stake: u64, 
     pub max_stake: u64, 
     pub emergency_cooldown: i64,
-    pub withdrawal_limit: u64,
-    pub voting_period: i64,
+    pub emergency_penalty: u64,
+    pub penalty_destination: PenaltyDestination,
+    pub unbonding_period: i64,
+    pub keeper_tip_bps: u64,
+    pub liquidity_buffer_bps: u64,
+    pub liquidity_buffer_floor: u64,
+    pub keeper: KeeperConfig,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct ConfigUpdate {
+    pub base_apy: Option<u64>,
+    pub min_stake: Option<u64>,
+    pub max_stake: Option<u64>,
+    pub emergency_cooldown: Option<i64>,
+    pub emergency_penalty: Option<u64>,
+    pub penalty_destination: Option<PenaltyDestination>,
+    pub unbonding_period: Option<i64>,
+    pub keeper_tip_bps: Option<u64>,
+    pub liquidity_buffer_bps: Option<u64>,
+    pub liquidity_buffer_floor: Option<u64>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PenaltyDestination {
+    Treasury,
+    Stakers,
 }
 
 #[account]
 #[derive(Debug)]
 pub struct StakingState {
     pub authority: Pubkey,
+    pub stake_mint: Pubkey,
     pub current_apy: u64,
     pub min_stake: u64,
     pub max_stake: u64,
     pub emergency_cooldown: i64,
     pub paused: bool,
     pub total_staked: u64,
+    pub reward_per_token: u128,
+    pub last_reward_update: i64,
+    pub emergency_penalty: u64,
+    pub penalty_destination: PenaltyDestination,
+    pub reward_reserve: u64,
+    pub total_rewards_distributed: u64,
+    pub reward_mode: RewardMode,
+    pub emission_rate: u64,
+    pub emission_step_interval: i64,
+    pub emission_step_percent: u64,
+    pub next_emission_step: i64,
+    pub bump: u8,
+    pub receipt_mint: Pubkey,
+    pub liquid_staked: u64,
+    pub liquid_reward_per_token_paid: u128,
+    pub liquid_pending_rewards: u64,
+    pub lock_tiers: [LockTier; MAX_LOCK_TIERS],
+    pub keeper_tip_bps: u64,
+    pub approved_depositors: [Pubkey; MAX_APPROVED_DEPOSITORS],
+    pub liquidity_buffer_bps: u64,
+    pub liquidity_buffer_floor: u64,
+    pub total_effective_stake: u64,
+    pub unbonding_period: i64,
+    pub total_unbonding: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitRoute {
+    Instant,
+    Queued,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct UnstakeOutcome {
+    pub route: ExitRoute,
+    pub amount: u64,
+    pub request: Option<u64>,
+    pub release_time: i64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompoundOutcome {
+    pub gross: u64,
+    pub fee: u64,
+    pub tip: u64,
+    pub compounded: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockTier {
+    pub duration: i64,
+    pub multiplier_bps: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LiquidExchangeRate {
+    pub total_underlying: u64,
+    pub receipt_supply: u64,
+    pub underlying_per_receipt: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RewardMode {
+    Apy,
+    Emission,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RewardRunway {
+    pub reward_reserve: u64,
+    pub daily_rewards: u64,
+    pub days_covered: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct WithdrawalHeadroom {
+    pub withdrawal_limit: u64,
+    pub withdrawal_period: i64,
+    pub window_spent: u64,
+    pub window_end: i64,
+    pub available: u64,
 }
 
 impl StakingState {
-    pub const LEN: usize = 32 + 8 + 8 + 8 + 8 + 1 + 8;
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 1 + 8 + 16 + 8 + 8 + 1 + 8 + 8 + 1 + 8 + 8 + 8 + 8 + 1 + 32 + 8 + 16 + 8 + 16 * MAX_LOCK_TIERS + 8 + 32 * MAX_APPROVED_DEPOSITORS + 8 + 8 + 8 + 8 + 8;
+
+    pub fn update_reward_index(&mut self, current_time: i64) -> Result<u128> {
+        let time_diff = current_time - self.last_reward_update;
+        if time_diff > 0 {
+            let increment = match self.reward_mode {
+                RewardMode::Apy => (self.current_apy as u128)
+                    .checked_mul(time_diff as u128)
+                    .ok_or(ProtocolError::Overflow)?
+                    .checked_mul(REWARD_PRECISION)
+                    .ok_or(ProtocolError::Overflow)?
+                    .checked_div(SECONDS_PER_YEAR * 100)
+                    .ok_or(ProtocolError::Overflow)?,
+                RewardMode::Emission => self.advance_emissions(current_time)?,
+            };
+
+            self.reward_per_token = self.reward_per_token
+                .checked_add(increment)
+                .ok_or(ProtocolError::Overflow)?;
+            self.last_reward_update = current_time;
+        }
+
+        Ok(self.reward_per_token)
+    }
+
+    // The step decay is summed as a geometric series so that the cost of catching up
+    // does not grow with the number of steps since the last update.
+    fn advance_emissions(&mut self, current_time: i64) -> Result<u128> {
+        let emitted = self.emitted_until(current_time)?;
+
+        if self.total_effective_stake == 0 {
+            return Ok(0);
+        }
+
+        Ok(emitted
+            .checked_mul(REWARD_PRECISION)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(self.total_effective_stake as u128)
+            .ok_or(ProtocolError::Overflow)?)
+    }
+
+    fn emitted_until(&mut self, current_time: i64) -> Result<u128> {
+        let start = self.last_reward_update;
+        let rate = self.emission_rate as u128;
+        if self.emission_step_interval <= 0 || current_time < self.next_emission_step {
+            return rate
+                .checked_mul((current_time - start) as u128)
+                .ok_or(error!(ProtocolError::Overflow));
+        }
+
+        let interval = self.emission_step_interval;
+        let since_step = current_time - self.next_emission_step;
+        let steps = since_step / interval + 1;
+        let partial = since_step % interval;
+        let keep_percent = 100 - self.emission_step_percent as u128;
+
+        // Up to the first step at the current rate.
+        let mut emitted = rate
+            .checked_mul((self.next_emission_step - start) as u128)
+            .ok_or(ProtocolError::Overflow)?;
+
+        // Whole intervals between steps: rate * interval * sum(f^i, i = 1..steps - 1),
+        // where f = keep_percent / 100 and the sum is f * (1 - f^(steps - 1)) / (1 - f).
+        if steps > 1 && keep_percent > 0 {
+            let whole_intervals = if keep_percent == 100 {
+                ((steps - 1) as u128)
+                    .checked_mul(EMISSION_DECAY_PRECISION)
+                    .ok_or(ProtocolError::Overflow)?
+            } else {
+                let remaining = EMISSION_DECAY_PRECISION
+                    .checked_sub(emission_decay(keep_percent, (steps - 1) as u64)?)
+                    .ok_or(ProtocolError::Overflow)?;
+                remaining
+                    .checked_mul(keep_percent)
+                    .and_then(|sum| sum.checked_div(100 - keep_percent))
+                    .ok_or(ProtocolError::Overflow)?
+            };
+            let full = rate
+                .checked_mul(interval as u128)
+                .and_then(|per_interval| per_interval.checked_mul(whole_intervals))
+                .and_then(|full| full.checked_div(EMISSION_DECAY_PRECISION))
+                .ok_or(ProtocolError::Overflow)?;
+            emitted = emitted.checked_add(full).ok_or(ProtocolError::Overflow)?;
+        }
+
+        let new_rate = rate
+            .checked_mul(emission_decay(keep_percent, steps as u64)?)
+            .and_then(|rate| rate.checked_div(EMISSION_DECAY_PRECISION))
+            .ok_or(ProtocolError::Overflow)?;
+        emitted = new_rate
+            .checked_mul(partial as u128)
+            .and_then(|tail| emitted.checked_add(tail))
+            .ok_or(ProtocolError::Overflow)?;
+
+        self.emission_rate = u64::try_from(new_rate).map_err(|_| error!(ProtocolError::Overflow))?;
+        self.next_emission_step = steps
+            .checked_mul(interval)
+            .and_then(|skipped| self.next_emission_step.checked_add(skipped))
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(emitted)
+    }
+
+    // Positions only earn the loyalty bonus on top of APY rewards; an emission
+    // schedule has a fixed budget that the bonus would overrun.
+    pub fn loyalty_bonus_active(&self) -> bool {
+        self.reward_mode == RewardMode::Apy
+    }
+
+    pub fn set_reward_mode(
+        &mut self,
+        mode: RewardMode,
+        emission_rate: u64,
+        step_interval: i64,
+        step_percent: u64,
+        current_time: i64,
+    ) -> Result<()> {
+        require!(
+            step_interval == 0 || step_interval >= MIN_EMISSION_STEP_INTERVAL,
+            ProtocolError::InvalidEmissionSchedule
+        );
+        require!(step_percent <= 100, ProtocolError::InvalidEmissionSchedule);
+
+        self.update_reward_index(current_time)?;
+
+        self.reward_mode = mode;
+        self.emission_rate = emission_rate;
+        self.emission_step_interval = step_interval;
+        self.emission_step_percent = step_percent;
+        self.next_emission_step = current_time
+            .checked_add(step_interval)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    pub fn default_lock_tiers() -> [LockTier; MAX_LOCK_TIERS] {
+        DEFAULT_LOCK_TIERS.map(|(duration, multiplier_bps)| LockTier { duration, multiplier_bps })
+    }
+
+    pub fn lock_tier(&self, tier: u8) -> Result<LockTier> {
+        self.lock_tiers
+            .get(tier as usize)
+            .copied()
+            .ok_or(error!(ProtocolError::InvalidLockTier))
+    }
+
+    pub fn set_lock_tier(&mut self, tier: u8, duration: i64, multiplier_bps: u64) -> Result<()> {
+        require!(
+            (0..=MAX_LOCK_DURATION).contains(&duration),
+            ProtocolError::InvalidLockDuration
+        );
+        require!(
+            (BPS_DENOMINATOR as u64..=MAX_LOCK_MULTIPLIER_BPS).contains(&multiplier_bps),
+            ProtocolError::InvalidLockTier
+        );
+
+        let entry = self.lock_tiers
+            .get_mut(tier as usize)
+            .ok_or(ProtocolError::InvalidLockTier)?;
+        entry.duration = duration;
+        entry.multiplier_bps = multiplier_bps;
+
+        Ok(())
+    }
+
+    pub fn set_approved_depositors(&mut self, programs: &[Pubkey]) -> Result<()> {
+        require!(
+            programs.len() <= MAX_APPROVED_DEPOSITORS,
+            ProtocolError::TooManyDepositors
+        );
+
+        self.approved_depositors = [Pubkey::default(); MAX_APPROVED_DEPOSITORS];
+        self.approved_depositors[..programs.len()].copy_from_slice(programs);
+
+        Ok(())
+    }
+
+    // An empty allowlist leaves delegated staking open to any calling program.
+    pub fn is_approved_depositor(&self, program: &Pubkey) -> bool {
+        let mut approved = self.approved_depositors
+            .iter()
+            .filter(|depositor| **depositor != Pubkey::default())
+            .peekable();
+
+        approved.peek().is_none() || approved.any(|depositor| depositor == program)
+    }
+
+    pub fn validate_config(&self) -> Result<()> {
+        require!(
+            (MIN_APY..=MAX_APY).contains(&self.current_apy),
+            ProtocolError::InvalidAPY
+        );
+        require!(
+            self.min_stake <= self.max_stake,
+            ProtocolError::InvalidStakeRange
+        );
+        require!(
+            self.emergency_cooldown >= 0 && self.unbonding_period >= 0,
+            ProtocolError::InvalidCooldown
+        );
+        require!(
+            self.emergency_penalty <= MAX_EMERGENCY_PENALTY,
+            ProtocolError::InvalidPenalty
+        );
+        require!(
+            self.keeper_tip_bps <= MAX_KEEPER_TIP_BPS,
+            ProtocolError::InvalidKeeperTip
+        );
+        require!(
+            self.liquidity_buffer_bps as u128 <= BPS_DENOMINATOR,
+            ProtocolError::InvalidLiquidityBuffer
+        );
+
+        Ok(())
+    }
+
+    // The vault keeps at least the larger of the share of total stake and the floor.
+    pub fn liquidity_buffer(&self) -> Result<u64> {
+        let share = (self.total_staked as u128)
+            .checked_mul(self.liquidity_buffer_bps as u128)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(BPS_DENOMINATOR)
+            .ok_or(ProtocolError::Overflow)?;
+        let share = u64::try_from(share).map_err(|_| error!(ProtocolError::Overflow))?;
+
+        Ok(share.max(self.liquidity_buffer_floor))
+    }
+
+    // Unbonding stakers and the withdrawal queue are both owed from the principal
+    // vault, so a payout must leave them covered on top of the liquidity buffer.
+    pub fn exit_covered(&self, vault_balance: u64, amount: u64, total_queued: u64) -> Result<bool> {
+        let required = [total_queued, self.total_unbonding, self.liquidity_buffer()?]
+            .into_iter()
+            .try_fold(amount, |required, owed| required.checked_add(owed))
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(vault_balance >= required)
+    }
+
+    pub fn add_unbonding(&mut self, amount: u64) -> Result<()> {
+        self.total_unbonding = self.total_unbonding
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    pub fn release_unbonding(&mut self, amount: u64) -> Result<()> {
+        self.total_unbonding = self.total_unbonding
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    pub fn keeper_tip(&self, amount: u64) -> Result<u64> {
+        let tip = (amount as u128)
+            .checked_mul(self.keeper_tip_bps as u128)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(BPS_DENOMINATOR)
+            .ok_or(ProtocolError::Overflow)?;
+
+        u64::try_from(tip).map_err(|_| error!(ProtocolError::Overflow))
+    }
+
+    pub fn release_rewards(&mut self, requested: u64) -> Result<u64> {
+        let amount = requested.min(self.reward_reserve);
+
+        self.reward_reserve = self.reward_reserve
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        self.total_rewards_distributed = self.total_rewards_distributed
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(amount)
+    }
+
+    pub fn reward_runway(&self) -> Result<RewardRunway> {
+        let daily_rewards = match self.reward_mode {
+            RewardMode::Apy => (self.total_effective_stake as u128)
+                .checked_mul(self.current_apy as u128)
+                .ok_or(ProtocolError::Overflow)?
+                .checked_mul(SECONDS_PER_DAY)
+                .ok_or(ProtocolError::Overflow)?
+                .checked_div(SECONDS_PER_YEAR * 100)
+                .ok_or(ProtocolError::Overflow)?,
+            RewardMode::Emission => (self.emission_rate as u128)
+                .checked_mul(SECONDS_PER_DAY)
+                .ok_or(ProtocolError::Overflow)?,
+        };
+        let daily_rewards = u64::try_from(daily_rewards).map_err(|_| error!(ProtocolError::Overflow))?;
+
+        let days_covered = self.reward_reserve
+            .checked_div(daily_rewards)
+            .unwrap_or(u64::MAX);
+
+        Ok(RewardRunway {
+            reward_reserve: self.reward_reserve,
+            daily_rewards,
+            days_covered,
+        })
+    }
+
+    pub fn calculate_emergency_penalty(&self, amount: u64) -> Result<u64> {
+        let penalty = (amount as u128)
+            .checked_mul(self.emergency_penalty as u128)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(100)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(penalty as u64)
+    }
+
+    pub fn distribute_to_stakers(&mut self, amount: u64) -> Result<()> {
+        require!(self.total_effective_stake > 0, ProtocolError::NoStakers);
+
+        let increment = (amount as u128)
+            .checked_mul(REWARD_PRECISION)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(self.total_effective_stake as u128)
+            .ok_or(ProtocolError::Overflow)?;
+
+        self.reward_per_token = self.reward_per_token
+            .checked_add(increment)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    pub fn liquid_enabled(&self) -> bool {
+        self.receipt_mint != Pubkey::default()
+    }
+
+    pub fn accrue_liquid_rewards(&mut self, reward_per_token: u128) -> Result<u64> {
+        let index_diff = reward_per_token
+            .checked_sub(self.liquid_reward_per_token_paid)
+            .ok_or(ProtocolError::Overflow)?;
+        let accrued = (self.liquid_staked as u128)
+            .checked_mul(index_diff)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(REWARD_PRECISION)
+            .ok_or(ProtocolError::Overflow)?;
+
+        self.liquid_pending_rewards = u64::try_from(accrued)
+            .ok()
+            .and_then(|accrued| self.liquid_pending_rewards.checked_add(accrued))
+            .ok_or(ProtocolError::Overflow)?;
+        self.liquid_reward_per_token_paid = reward_per_token;
+
+        Ok(self.liquid_pending_rewards)
+    }
+
+    // Liquid stake is unboosted, so it weighs the same as its amount.
+    pub fn add_liquid_stake(&mut self, amount: u64) -> Result<()> {
+        self.liquid_staked = self.liquid_staked
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        self.total_staked = self.total_staked
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        self.reweight_stake(0, amount)
+    }
+
+    pub fn remove_liquid_stake(&mut self, amount: u64) -> Result<()> {
+        self.liquid_staked = self.liquid_staked
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        self.total_staked = self.total_staked
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        self.reweight_stake(amount, 0)
+    }
+
+    // Emissions and redistributed penalties are shared by multiplier-weighted stake, so
+    // every change to a position's effective stake has to be mirrored here.
+    pub fn reweight_stake(&mut self, before: u64, after: u64) -> Result<()> {
+        self.total_effective_stake = self.total_effective_stake
+            .checked_sub(before)
+            .and_then(|total| total.checked_add(after))
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
 
-    pub fn calculate_rewards(&self, amount: u64, time_diff: i64) -> Result<u64> {
-        let base_reward = (amount as u128)
-            .checked_mul(self.current_apy as u128)
+    // Both conversions round down so that rounding always favours the vault.
+    pub fn receipts_for_deposit(&self, amount: u64, receipt_supply: u64) -> Result<u64> {
+        if receipt_supply == 0 || self.liquid_staked == 0 {
+            return Ok(amount);
+        }
+
+        let receipts = (amount as u128)
+            .checked_mul(receipt_supply as u128)
             .ok_or(ProtocolError::Overflow)?
-            .checked_mul(time_diff as u128)
+            .checked_div(self.liquid_staked as u128)
+            .ok_or(ProtocolError::Overflow)?;
+
+        u64::try_from(receipts).map_err(|_| error!(ProtocolError::Overflow))
+    }
+
+    pub fn underlying_for_receipts(&self, receipts: u64, receipt_supply: u64) -> Result<u64> {
+        let underlying = (receipts as u128)
+            .checked_mul(self.liquid_staked as u128)
             .ok_or(ProtocolError::Overflow)?
-            .checked_div(365 * 24 * 60 * 60 * 100)
+            .checked_div(receipt_supply as u128)
             .ok_or(ProtocolError::Overflow)?;
 
-        Ok(base_reward as u64)
+        u64::try_from(underlying).map_err(|_| error!(ProtocolError::Overflow))
+    }
+
+    // Mirrors compound_liquid_rewards, so the protocol fee is taken before the
+    // pending rewards are counted as liquid stake.
+    pub fn liquid_exchange_rate(
+        &self,
+        current_time: i64,
+        receipt_supply: u64,
+        treasury_state: &TreasuryState,
+    ) -> Result<LiquidExchangeRate> {
+        let mut preview = self.clone();
+        let reward_per_token = preview.update_reward_index(current_time)?;
+        let pending = preview.accrue_liquid_rewards(reward_per_token)?;
+        let released = preview.release_rewards(pending)?;
+        let fee = treasury_state.protocol_fee(released)?;
+        let compounded = released.checked_sub(fee).ok_or(ProtocolError::Overflow)?;
+        preview.add_liquid_stake(compounded)?;
+
+        let underlying_per_receipt = if receipt_supply == 0 {
+            EXCHANGE_RATE_PRECISION as u64
+        } else {
+            let rate = (preview.liquid_staked as u128)
+                .checked_mul(EXCHANGE_RATE_PRECISION)
+                .ok_or(ProtocolError::Overflow)?
+                .checked_div(receipt_supply as u128)
+                .ok_or(ProtocolError::Overflow)?;
+            u64::try_from(rate).map_err(|_| error!(ProtocolError::Overflow))?
+        };
+
+        Ok(LiquidExchangeRate {
+            total_underlying: preview.liquid_staked,
+            receipt_supply,
+            underlying_per_receipt,
+        })
+    }
+}
+
+// `keep_percent / 100` raised to `steps`, scaled by EMISSION_DECAY_PRECISION.
+fn emission_decay(keep_percent: u128, mut steps: u64) -> Result<u128> {
+    let mut factor = keep_percent
+        .checked_mul(EMISSION_DECAY_PRECISION)
+        .ok_or(ProtocolError::Overflow)?
+        / 100;
+    let mut result = EMISSION_DECAY_PRECISION;
+
+    while steps > 0 && result > 0 {
+        if steps & 1 == 1 {
+            result = result
+                .checked_mul(factor)
+                .ok_or(ProtocolError::Overflow)?
+                / EMISSION_DECAY_PRECISION;
+        }
+        factor = factor
+            .checked_mul(factor)
+            .ok_or(ProtocolError::Overflow)?
+            / EMISSION_DECAY_PRECISION;
+        steps >>= 1;
     }
+
+    Ok(result)
 }
 
 #[account]
 #[derive(Debug)]
 pub struct UserStakeInfo {
     pub owner: Pubkey,
+    pub pool: Pubkey,
     pub staked_amount: u64,
-    pub last_stake_timestamp: i64,
-    pub rewards_claimed: u64,
+    pub position_count: u64,
+    pub unbonding_amount: u64,
+    pub unbonding_release_time: i64,
+    pub last_emergency_withdraw: i64,
 }
 
 impl UserStakeInfo {
-    pub const LEN: usize = 32 + 8 + 8 + 8;
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8;
 
-    pub fn calculate_rewards(&self, current_time: i64, apy: u64) -> Result<u64> {
-        let time_diff = current_time - self.last_stake_timestamp;
-        if time_diff <= 0 {
-            return Ok(0);
-        }
+    pub fn next_position_index(&mut self) -> Result<u64> {
+        let index = self.position_count;
+        self.position_count = self.position_count
+            .checked_add(1)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(index)
+    }
+
+    // Only one unbonding at a time, so a new request can never push back the
+    // release of tokens that are already unbonding.
+    pub fn start_unbonding(&mut self, amount: u64, release_time: i64) -> Result<()> {
+        require!(self.unbonding_amount == 0, ProtocolError::UnbondingInProgress);
 
-        let base_reward = (self.staked_amount as u128)
-            .checked_mul(apy as u128)
+        self.unbonding_amount = amount;
+        self.unbonding_release_time = release_time;
+
+        Ok(())
+    }
+
+    pub fn can_emergency_withdraw(&self, current_time: i64, cooldown: i64) -> bool {
+        current_time - self.last_emergency_withdraw >= cooldown
+    }
+}
+
+#[account]
+#[derive(Debug)]
+pub struct StakePosition {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    pub index: u64,
+    pub amount: u64,
+    pub start_time: i64,
+    pub lock_end: i64,
+    pub lock_tier: u8,
+    pub lock_duration: i64,
+    pub multiplier_bps: u64,
+    pub auto_compound: bool,
+    pub reward_per_token_paid: u128,
+    pub pending_rewards: u64,
+    pub rewards_claimed: u64,
+    pub bump: u8,
+}
+
+impl StakePosition {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 1 + 16 + 8 + 8 + 1;
+
+    pub fn apply_lock_tier(&mut self, tier: u8, lock_tier: LockTier) {
+        self.lock_tier = tier;
+        self.lock_duration = lock_tier.duration;
+        self.multiplier_bps = lock_tier.multiplier_bps;
+    }
+
+    // Every deposit restarts the lock for the position's full tier duration.
+    pub fn extend_lock(&mut self, current_time: i64) -> Result<()> {
+        let lock_end = current_time
+            .checked_add(self.lock_duration)
+            .ok_or(ProtocolError::Overflow)?;
+        self.lock_end = self.lock_end.max(lock_end);
+
+        Ok(())
+    }
+
+    pub fn effective_stake(&self) -> Result<u64> {
+        let effective = (self.amount as u128)
+            .checked_mul(self.multiplier_bps as u128)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(BPS_DENOMINATOR)
+            .ok_or(ProtocolError::Overflow)?;
+
+        u64::try_from(effective).map_err(|_| error!(ProtocolError::Overflow))
+    }
+
+    pub fn calculate_rewards(&self, current_time: i64, reward_per_token: u128, loyalty_bonus: bool) -> Result<u64> {
+        let index_diff = reward_per_token
+            .checked_sub(self.reward_per_token_paid)
+            .ok_or(ProtocolError::Overflow)?;
+
+        let base_reward = (self.amount as u128)
+            .checked_mul(index_diff)
             .ok_or(ProtocolError::Overflow)?
-            .checked_mul(time_diff as u128)
+            .checked_div(REWARD_PRECISION)
+            .ok_or(ProtocolError::Overflow)?;
+
+        let boosted_reward = (self.effective_stake()? as u128)
+            .checked_mul(index_diff)
             .ok_or(ProtocolError::Overflow)?
-            .checked_div(365 * 24 * 60 * 60 * 100)
+            .checked_div(REWARD_PRECISION)
             .ok_or(ProtocolError::Overflow)?;
 
-        let bonus = if time_diff > 30 * 24 * 60 * 60 {
+        let bonus = if loyalty_bonus && current_time - self.start_time > LOYALTY_BONUS_PERIOD {
             base_reward
-                .checked_mul(10)
+                .checked_mul(LOYALTY_BONUS_PERCENT)
                 .ok_or(ProtocolError::Overflow)?
                 .checked_div(100)
                 .ok_or(ProtocolError::Overflow)?
@@ -75,70 +730,1635 @@ impl UserStakeInfo {
             0
         };
 
-        Ok(base_reward.checked_add(bonus).ok_or(ProtocolError::Overflow)? as u64)
+        let total = boosted_reward.checked_add(bonus).ok_or(ProtocolError::Overflow)?;
+        u64::try_from(total).map_err(|_| error!(ProtocolError::Overflow))
     }
-}
 
-#[account]
-#[derive(Debug)]
-pub struct TreasuryState {
-    pub authority: Pubkey,
-    pub withdrawal_limit: u64,
-    pub required_signatures: u8,
-    pub total_balance: u64,
-}
+    pub fn checkpoint(&mut self, reward_per_token: u128) {
+        self.reward_per_token_paid = reward_per_token;
+    }
 
-impl TreasuryState {
-    pub const LEN: usize = 32 + 8 + 1 + 8;
-}
+    pub fn settle_rewards(&mut self, current_time: i64, reward_per_token: u128, loyalty_bonus: bool) -> Result<u64> {
+        let rewards = self.calculate_rewards(current_time, reward_per_token, loyalty_bonus)?;
+        self.pending_rewards = self.pending_rewards
+            .checked_add(rewards)
+            .ok_or(ProtocolError::Overflow)?;
+        self.checkpoint(reward_per_token);
 
-#[account]
-#[derive(Debug)]
-pub struct WithdrawalState {
-    pub requests: Vec<WithdrawalRequest>,
-    pub last_processed_time: i64,
-}
+        Ok(self.pending_rewards)
+    }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub struct WithdrawalRequest {
-    pub user: Pubkey,
-    pub amount: u64,
-    pub request_time: i64,
-    pub processed: bool,
-}
+    pub fn record_reward_payout(&mut self, amount: u64) -> Result<()> {
+        self.pending_rewards = self.pending_rewards
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        self.rewards_claimed = self.rewards_claimed
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
 
-impl WithdrawalState {
-    pub fn add_request(&mut self, request: WithdrawalRequest) -> Result<()> {
-        self.requests.push(request);
         Ok(())
     }
 
-    pub fn process_batch(&mut self, current_time: i64) -> Vec<WithdrawalRequest> {
-        let mut to_process = Vec::new();
-        for request in self.requests.iter_mut() {
-            if !request.processed && request.request_time <= current_time {
-                request.processed = true;
-                to_process.push(request.clone());
-            }
-        }
-        self.last_processed_time = current_time;
-        to_process
+    pub fn is_locked(&self, current_time: i64) -> bool {
+        current_time < self.lock_end
     }
 
-    pub fn clean_processed(&mut self) {
-        self.requests.retain(|r| !r.processed);
+    pub fn is_empty(&self) -> bool {
+        self.amount == 0 && self.pending_rewards == 0
     }
-}
 
-#[account]
-#[derive(Debug)]
-pub struct GovernanceState {
-    pub current_apy: u64,
+    // Both positions must be settled at the same index before merging. The merged
+    // position keeps the later start time and the later lock expiry.
+    pub fn absorb(&mut self, other: &StakePosition) -> Result<()> {
+        require!(
+            self.lock_tier == other.lock_tier && self.multiplier_bps == other.multiplier_bps,
+            ProtocolError::IncompatiblePositions
+        );
+
+        self.amount = self.amount
+            .checked_add(other.amount)
+            .ok_or(ProtocolError::Overflow)?;
+        self.pending_rewards = self.pending_rewards
+            .checked_add(other.pending_rewards)
+            .ok_or(ProtocolError::Overflow)?;
+        self.rewards_claimed = self.rewards_claimed
+            .checked_add(other.rewards_claimed)
+            .ok_or(ProtocolError::Overflow)?;
+        self.start_time = self.start_time.max(other.start_time);
+        self.lock_end = self.lock_end.max(other.lock_end);
+
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct KeeperConfig {
+    pub tip_amount: u64,
+    pub min_interval: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeeperTask {
+    ProcessQueue,
+    RewardIndex,
+    RewardTopUp,
+    Compound,
+}
+
+#[account]
+#[derive(Debug)]
+pub struct KeeperState {
+    pub pool: Pubkey,
+    pub tip_amount: u64,
+    pub min_interval: i64,
+    pub reward_topup_amount: u64,
+    pub reward_topup_threshold: u64,
+    pub last_queue_process: i64,
+    pub last_reward_update: i64,
+    pub last_reward_topup: i64,
+    pub last_compound: i64,
+    pub total_tips_paid: u64,
+    pub bump: u8,
+}
+
+impl KeeperState {
+    pub const LEN: usize = 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1;
+
+    pub fn apply_config(&mut self, config: &KeeperConfig) -> Result<()> {
+        require!(config.min_interval >= 0, ProtocolError::InvalidKeeperConfig);
+
+        self.tip_amount = config.tip_amount;
+        self.min_interval = config.min_interval;
+
+        Ok(())
+    }
+
+    // Top-ups move treasury funds, so only an executed treasury transaction sets them.
+    pub fn set_reward_topup(&mut self, amount: u64, threshold: u64, withdrawal_limit: u64) -> Result<()> {
+        require!(amount <= withdrawal_limit, ProtocolError::WithdrawalLimitExceeded);
+
+        self.reward_topup_amount = amount;
+        self.reward_topup_threshold = threshold;
+
+        Ok(())
+    }
+
+    // Each task earns at most one tip per `min_interval`, and the return value says
+    // whether this run is due one. The idempotent cranks are rejected inside the
+    // interval; queue processing and compounding still run back to back so a backlog
+    // can drain, but only the first run of each interval is tipped.
+    pub fn record_run(&mut self, task: KeeperTask, current_time: i64) -> Result<bool> {
+        let last_run = match task {
+            KeeperTask::ProcessQueue => &mut self.last_queue_process,
+            KeeperTask::RewardIndex => &mut self.last_reward_update,
+            KeeperTask::RewardTopUp => &mut self.last_reward_topup,
+            KeeperTask::Compound => &mut self.last_compound,
+        };
+        let tip_due = current_time.saturating_sub(*last_run) >= self.min_interval;
+
+        if matches!(task, KeeperTask::RewardIndex | KeeperTask::RewardTopUp) {
+            require!(tip_due, ProtocolError::CrankTooSoon);
+        }
+        if tip_due {
+            *last_run = current_time;
+        }
+
+        Ok(tip_due)
+    }
+
+    pub fn record_tip(&mut self, tip: u64) -> Result<()> {
+        self.total_tips_paid = self.total_tips_paid
+            .checked_add(tip)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BondMarketConfig {
+    pub control_variable: u64,
+    pub min_price: u64,
+    pub capacity: u64,
+    pub vesting_term: i64,
+    pub max_price_age: i64,
+    pub max_confidence_bps: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct BondAdjustment {
+    pub add: bool,
+    pub rate: u64,
+    pub target: u64,
+    pub buffer: i64,
+    pub last_time: i64,
+}
+
+#[account]
+#[derive(Debug)]
+pub struct BondMarket {
+    pub quote_mint: Pubkey,
+    pub payout_mint: Pubkey,
+    pub control_variable: u64,
+    pub min_price: u64,
+    pub capacity: u64,
+    pub sold: u64,
+    pub total_debt: u64,
+    pub last_decay: i64,
+    pub vesting_term: i64,
+    pub outstanding_payout: u64,
+    pub total_quote_received: u64,
+    pub adjustment: BondAdjustment,
+    pub price_feed: Pubkey,
+    pub max_price_age: i64,
+    pub max_confidence_bps: u64,
+    pub bump: u8,
+}
+
+impl BondMarket {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + (1 + 8 + 8 + 8 + 8) + 32 + 8 + 8 + 1;
+
+    pub fn has_price_feed(&self) -> bool {
+        self.price_feed != Pubkey::default()
+    }
+
+    pub fn apply_config(&mut self, config: &BondMarketConfig) -> Result<()> {
+        require!(
+            config.control_variable > 0
+                && config.min_price > 0
+                && config.capacity > 0
+                && config.vesting_term >= MIN_BOND_VESTING_TERM
+                && config.max_price_age >= 0
+                && config.max_confidence_bps as u128 <= BPS_DENOMINATOR,
+            ProtocolError::InvalidBondConfig
+        );
+
+        self.control_variable = config.control_variable;
+        self.min_price = config.min_price;
+        self.capacity = config.capacity;
+        self.vesting_term = config.vesting_term;
+        self.max_price_age = config.max_price_age;
+        self.max_confidence_bps = config.max_confidence_bps;
+
+        Ok(())
+    }
+
+    pub fn set_adjustment(&mut self, add: bool, rate: u64, target: u64, buffer: i64, current_time: i64) -> Result<()> {
+        let max_rate = (self.control_variable as u128)
+            .checked_mul(MAX_BOND_ADJUSTMENT_BPS as u128)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(BPS_DENOMINATOR)
+            .ok_or(ProtocolError::Overflow)?;
+        require!(
+            rate as u128 <= max_rate && target > 0 && buffer >= 0,
+            ProtocolError::InvalidBondConfig
+        );
+
+        self.adjustment = BondAdjustment {
+            add,
+            rate,
+            target,
+            buffer,
+            last_time: current_time,
+        };
+
+        Ok(())
+    }
+
+    // Debt runs off linearly over one vesting term, which lets the price fall back
+    // towards the minimum when demand dries up.
+    pub fn decay_debt(&mut self, current_time: i64) -> Result<()> {
+        let elapsed = current_time.saturating_sub(self.last_decay).max(0);
+        let decay = (self.total_debt as u128)
+            .checked_mul(elapsed as u128)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(self.vesting_term as u128)
+            .ok_or(ProtocolError::Overflow)?
+            .min(self.total_debt as u128);
+
+        self.total_debt = self.total_debt
+            .checked_sub(decay as u64)
+            .ok_or(ProtocolError::Overflow)?;
+        self.last_decay = current_time;
+
+        Ok(())
+    }
+
+    // Steps the control variable towards its target once per buffer interval.
+    pub fn adjust(&mut self, current_time: i64) -> Result<bool> {
+        let adjustment = &mut self.adjustment;
+        let next_time = adjustment.last_time
+            .checked_add(adjustment.buffer)
+            .ok_or(ProtocolError::Overflow)?;
+        if adjustment.rate == 0 || current_time < next_time {
+            return Ok(false);
+        }
+
+        if adjustment.add {
+            self.control_variable = self.control_variable
+                .saturating_add(adjustment.rate)
+                .min(adjustment.target);
+            if self.control_variable >= adjustment.target {
+                adjustment.rate = 0;
+            }
+        } else {
+            self.control_variable = self.control_variable
+                .saturating_sub(adjustment.rate)
+                .max(adjustment.target);
+            if self.control_variable <= adjustment.target {
+                adjustment.rate = 0;
+            }
+        }
+        adjustment.last_time = current_time;
+
+        Ok(true)
+    }
+
+    pub fn debt_ratio(&self, payout_supply: u64) -> Result<u128> {
+        if payout_supply == 0 {
+            return Ok(0);
+        }
+
+        (self.total_debt as u128)
+            .checked_mul(DEBT_RATIO_PRECISION)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(payout_supply as u128)
+            .ok_or(error!(ProtocolError::Overflow))
+    }
+
+    // Price in quote units per payout unit, scaled by BOND_PRICE_PRECISION.
+    pub fn bond_price(&self, payout_supply: u64) -> Result<u64> {
+        let price = (self.control_variable as u128)
+            .checked_mul(self.debt_ratio(payout_supply)?)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(DEBT_RATIO_PRECISION)
+            .ok_or(ProtocolError::Overflow)?;
+        let price = u64::try_from(price).map_err(|_| error!(ProtocolError::Overflow))?;
+
+        Ok(price.max(self.min_price))
+    }
+
+    pub fn payout_for(&self, value: u64, price: u64) -> Result<u64> {
+        let payout = (value as u128)
+            .checked_mul(BOND_PRICE_PRECISION)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(price as u128)
+            .ok_or(ProtocolError::Overflow)?;
+
+        u64::try_from(payout).map_err(|_| error!(ProtocolError::Overflow))
+    }
+
+    pub fn record_bond(&mut self, value: u64, payout: u64) -> Result<()> {
+        let sold = self.sold
+            .checked_add(payout)
+            .ok_or(ProtocolError::Overflow)?;
+        require!(sold <= self.capacity, ProtocolError::BondCapacityExceeded);
+
+        self.sold = sold;
+        self.total_debt = self.total_debt
+            .checked_add(payout)
+            .ok_or(ProtocolError::Overflow)?;
+        self.outstanding_payout = self.outstanding_payout
+            .checked_add(payout)
+            .ok_or(ProtocolError::Overflow)?;
+        self.total_quote_received = self.total_quote_received
+            .checked_add(value)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    pub fn record_payout(&mut self, payout: u64) -> Result<()> {
+        self.outstanding_payout = self.outstanding_payout
+            .checked_sub(payout)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+}
+
+// Pyth-style price of one whole `mint` token in whole payout tokens: `price * 10^expo`,
+// with `conf` the confidence interval in the same units.
+#[account]
+#[derive(Debug)]
+pub struct PriceFeed {
+    pub authority: Pubkey,
+    pub mint: Pubkey,
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+    pub bump: u8,
+}
+
+impl PriceFeed {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 4 + 8 + 1;
+
+    pub fn validate(&self, current_time: i64, max_age: i64, max_confidence_bps: u64) -> Result<u64> {
+        let price = u64::try_from(self.price)
+            .ok()
+            .filter(|price| *price > 0)
+            .ok_or(ProtocolError::InvalidPriceFeed)?;
+
+        require!(
+            current_time.saturating_sub(self.publish_time) <= max_age,
+            ProtocolError::StalePrice
+        );
+        require!(
+            (self.conf as u128)
+                .checked_mul(BPS_DENOMINATOR)
+                .ok_or(ProtocolError::Overflow)?
+                <= (price as u128)
+                    .checked_mul(max_confidence_bps as u128)
+                    .ok_or(ProtocolError::Overflow)?,
+            ProtocolError::PriceConfidenceTooLow
+        );
+
+        Ok(price)
+    }
+
+    // Converts base units of the priced mint into base units of the payout mint.
+    pub fn value_of(&self, amount: u64, price: u64, quote_decimals: u8, payout_decimals: u8) -> Result<u64> {
+        let value = (amount as u128)
+            .checked_mul(price as u128)
+            .ok_or(ProtocolError::Overflow)?;
+
+        rescale(value, self.expo, quote_decimals, payout_decimals)
+    }
+}
+
+// Multiplies `value` by 10^expo and moves it from `from_decimals` to `to_decimals`, rounding down.
+pub fn rescale(value: u128, expo: i32, from_decimals: u8, to_decimals: u8) -> Result<u64> {
+    let exponent = expo
+        .checked_add(to_decimals as i32)
+        .and_then(|exponent| exponent.checked_sub(from_decimals as i32))
+        .ok_or(ProtocolError::Overflow)?;
+    let scale = 10u128
+        .checked_pow(exponent.unsigned_abs())
+        .ok_or(ProtocolError::Overflow)?;
+
+    let value = if exponent >= 0 {
+        value.checked_mul(scale).ok_or(ProtocolError::Overflow)?
+    } else {
+        value.checked_div(scale).ok_or(ProtocolError::Overflow)?
+    };
+
+    u64::try_from(value).map_err(|_| error!(ProtocolError::Overflow))
+}
+
+#[account]
+#[derive(Debug)]
+pub struct UserBondInfo {
+    pub owner: Pubkey,
+    pub market: Pubkey,
+    pub bond_count: u64,
+}
+
+impl UserBondInfo {
+    pub const LEN: usize = 32 + 32 + 8;
+
+    pub fn next_bond_index(&mut self) -> Result<u64> {
+        let index = self.bond_count;
+        self.bond_count = self.bond_count
+            .checked_add(1)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(index)
+    }
+}
+
+#[account]
+#[derive(Debug)]
+pub struct Bond {
+    pub owner: Pubkey,
+    pub market: Pubkey,
+    pub index: u64,
+    pub deposit: u64,
+    pub payout: u64,
+    pub price: u64,
+    pub created_at: i64,
+    pub maturity: i64,
+    pub vested_paid: u64,
+    pub bump: u8,
+}
+
+impl Bond {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1;
+
+    // Payout vests linearly from creation until maturity.
+    pub fn vested(&self, current_time: i64) -> Result<u64> {
+        if current_time >= self.maturity {
+            return Ok(self.payout);
+        }
+
+        let elapsed = current_time.saturating_sub(self.created_at).max(0);
+        let term = self.maturity
+            .checked_sub(self.created_at)
+            .ok_or(ProtocolError::Overflow)?;
+        let vested = (self.payout as u128)
+            .checked_mul(elapsed as u128)
+            .ok_or(ProtocolError::Overflow)?
+            .checked_div(term as u128)
+            .ok_or(ProtocolError::Overflow)?;
+
+        u64::try_from(vested).map_err(|_| error!(ProtocolError::Overflow))
+    }
+
+    pub fn redeemable(&self, current_time: i64) -> Result<u64> {
+        self.vested(current_time)?
+            .checked_sub(self.vested_paid)
+            .ok_or(error!(ProtocolError::Overflow))
+    }
+
+    pub fn record_redemption(&mut self, amount: u64) -> Result<()> {
+        self.vested_paid = self.vested_paid
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    pub fn fully_redeemed(&self) -> bool {
+        self.vested_paid >= self.payout
+    }
+}
+
+#[account]
+#[derive(Debug)]
+pub struct TreasuryState {
+    pub authority: Pubkey,
+    pub epoch_outflow_cap: u64,
+    pub epoch_duration: i64,
+    pub epoch_start: i64,
+    pub epoch_outflow: u64,
+    pub required_signatures: u8,
+    pub signers: [Pubkey; MAX_TREASURY_SIGNERS],
+    pub signer_count: u8,
+    pub owner_set_seqno: u32,
+    pub transaction_count: u64,
+    pub protocol_fee_bps: u64,
+}
+
+impl TreasuryState {
+    pub const LEN: usize = 32 + 8 + 8 + 8 + 8 + 1 + 32 * MAX_TREASURY_SIGNERS + 1 + 4 + 8 + 8;
+
+    pub fn set_protocol_fee(&mut self, fee_bps: u64) -> Result<()> {
+        require!(fee_bps <= MAX_PROTOCOL_FEE_BPS, ProtocolError::InvalidProtocolFee);
+        self.protocol_fee_bps = fee_bps;
+        Ok(())
+    }
+
+    pub fn protocol_fee(&self, rewards: u64) -> Result<u64> {
+        let fee = (rewards as u128)
+            .checked_mul(self.protocol_fee_bps as u128)
+            .and_then(|fee| fee.checked_div(BPS_DENOMINATOR))
+            .ok_or(ProtocolError::Overflow)?;
+
+        u64::try_from(fee).map_err(|_| error!(ProtocolError::Overflow))
+    }
+
+    pub fn set_outflow_cap(&mut self, cap: u64, epoch_duration: i64, current_time: i64) -> Result<()> {
+        require!(epoch_duration > 0, ProtocolError::InvalidOutflowEpoch);
+
+        self.epoch_outflow_cap = cap;
+        self.epoch_duration = epoch_duration;
+        self.epoch_start = current_time;
+        self.epoch_outflow = 0;
+
+        Ok(())
+    }
+
+    // A cap of zero disables the limit.
+    pub fn outflow_headroom(&mut self, current_time: i64) -> Result<u64> {
+        self.roll_epoch(current_time)?;
+
+        if self.epoch_outflow_cap == 0 {
+            return Ok(u64::MAX);
+        }
+        Ok(self.epoch_outflow_cap.saturating_sub(self.epoch_outflow))
+    }
+
+    pub fn record_outflow(&mut self, amount: u64, current_time: i64) -> Result<()> {
+        require!(
+            amount <= self.outflow_headroom(current_time)?,
+            ProtocolError::OutflowCapReached
+        );
+
+        self.epoch_outflow = self.epoch_outflow
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    fn roll_epoch(&mut self, current_time: i64) -> Result<()> {
+        let elapsed = current_time.saturating_sub(self.epoch_start);
+        if self.epoch_duration <= 0 || elapsed < self.epoch_duration {
+            return Ok(());
+        }
+
+        let skipped = elapsed
+            .checked_div(self.epoch_duration)
+            .and_then(|epochs| epochs.checked_mul(self.epoch_duration))
+            .ok_or(ProtocolError::Overflow)?;
+        self.epoch_start = self.epoch_start
+            .checked_add(skipped)
+            .ok_or(ProtocolError::Overflow)?;
+        self.epoch_outflow = 0;
+
+        Ok(())
+    }
+
+    // Bumping the seqno invalidates approvals gathered under the previous signer set.
+    pub fn set_signers(&mut self, signers: &[Pubkey], threshold: u8) -> Result<()> {
+        require!(
+            !signers.is_empty() && signers.len() <= MAX_TREASURY_SIGNERS,
+            ProtocolError::InvalidTreasurySigners
+        );
+        require!(
+            threshold > 0 && threshold as usize <= signers.len(),
+            ProtocolError::InvalidTreasurySigners
+        );
+        for (i, signer) in signers.iter().enumerate() {
+            require!(
+                *signer != Pubkey::default() && !signers[..i].contains(signer),
+                ProtocolError::InvalidTreasurySigners
+            );
+        }
+
+        self.signers = [Pubkey::default(); MAX_TREASURY_SIGNERS];
+        self.signers[..signers.len()].copy_from_slice(signers);
+        self.signer_count = signers.len() as u8;
+        self.required_signatures = threshold;
+        self.owner_set_seqno = self.owner_set_seqno.wrapping_add(1);
+
+        Ok(())
+    }
+
+    pub fn active_signers(&self) -> &[Pubkey] {
+        &self.signers[..self.signer_count as usize]
+    }
+
+    pub fn signer_index(&self, signer: &Pubkey) -> Result<usize> {
+        self.active_signers()
+            .iter()
+            .position(|owner| owner == signer)
+            .ok_or(error!(ProtocolError::NotTreasurySigner))
+    }
+
+    pub fn next_transaction_index(&mut self) -> Result<u64> {
+        let index = self.transaction_count;
+        self.transaction_count = self.transaction_count
+            .checked_add(1)
+            .ok_or(ProtocolError::Overflow)?;
+        Ok(index)
+    }
+}
+
+// Treasury holdings in one mint: a vault owned by the treasury PDA plus its
+// recorded balance, income split by source and its own withdrawal limit. A new
+// ledger allows no withdrawals until governance sets a limit for its mint.
+#[account]
+#[derive(Debug)]
+pub struct TreasuryLedger {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub total_balance: u64,
+    pub deposit_income: u64,
+    pub penalty_income: u64,
+    pub fee_income: u64,
+    pub bond_income: u64,
+    pub bond_liabilities: u64,
+    pub withdrawal_limit: u64,
+    pub withdrawal_period: i64,
+    pub window_start: i64,
+    pub window_spent: u64,
+    pub previous_window_spent: u64,
+    pub bump: u8,
+}
+
+impl TreasuryLedger {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1;
+
+    pub fn record_income(&mut self, income: TreasuryIncome, amount: u64) -> Result<()> {
+        let counter = match income {
+            TreasuryIncome::Deposit => &mut self.deposit_income,
+            TreasuryIncome::Penalty => &mut self.penalty_income,
+            TreasuryIncome::ProtocolFee => &mut self.fee_income,
+            TreasuryIncome::BondProceeds => &mut self.bond_income,
+        };
+        *counter = counter.checked_add(amount).ok_or(ProtocolError::Overflow)?;
+        self.total_balance = self.total_balance
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    // Saturates because untracked transfers into the vault can leave the recorded
+    // balance behind the real one until the next sync.
+    pub fn record_spend(&mut self, amount: u64) {
+        self.total_balance = self.total_balance.saturating_sub(amount);
+    }
+
+    // Payouts owed to unredeemed bonds in every market paying out of this vault.
+    pub fn reserve_bond_payout(&mut self, vault_balance: u64, payout: u64) -> Result<()> {
+        let liabilities = self.bond_liabilities
+            .checked_add(payout)
+            .ok_or(ProtocolError::Overflow)?;
+        require!(vault_balance >= liabilities, ProtocolError::InsufficientBalance);
+
+        self.bond_liabilities = liabilities;
+
+        Ok(())
+    }
+
+    pub fn release_bond_payout(&mut self, payout: u64) -> Result<()> {
+        self.bond_liabilities = self.bond_liabilities
+            .checked_sub(payout)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    // Anything leaving the vault other than a bond redemption must leave every
+    // outstanding bond payout covered.
+    pub fn require_unreserved(&self, vault_balance: u64, amount: u64) -> Result<()> {
+        require!(
+            vault_balance.saturating_sub(self.bond_liabilities) >= amount,
+            ProtocolError::InsufficientBalance
+        );
+
+        Ok(())
+    }
+
+    pub fn sync_balance(&mut self, actual_balance: u64) -> i128 {
+        let difference = actual_balance as i128 - self.total_balance as i128;
+        self.total_balance = actual_balance;
+        difference
+    }
+
+    pub fn set_withdrawal_limit(&mut self, limit: u64, period: i64) -> Result<()> {
+        require!(period > 0, ProtocolError::InvalidWithdrawalPeriod);
+
+        self.withdrawal_limit = limit;
+        self.withdrawal_period = period;
+
+        Ok(())
+    }
+
+    // Returns (window_start, previous_window_spent, window_spent) as of `current_time`,
+    // rolling forward over any fixed `withdrawal_period` windows that have closed.
+    fn withdrawal_window(&self, current_time: i64) -> Result<(i64, u64, u64)> {
+        let elapsed = current_time.saturating_sub(self.window_start);
+        if self.withdrawal_period <= 0 || elapsed < self.withdrawal_period {
+            return Ok((self.window_start, self.previous_window_spent, self.window_spent));
+        }
+
+        let windows = elapsed
+            .checked_div(self.withdrawal_period)
+            .ok_or(ProtocolError::Overflow)?;
+        let window_start = windows
+            .checked_mul(self.withdrawal_period)
+            .and_then(|skipped| self.window_start.checked_add(skipped))
+            .ok_or(ProtocolError::Overflow)?;
+        let previous_window_spent = if windows == 1 { self.window_spent } else { 0 };
+
+        Ok((window_start, previous_window_spent, 0))
+    }
+
+    // The previous window's spend stays counted until the current window closes. Any
+    // trailing `withdrawal_period` overlaps at most those two windows, so capping their
+    // sum caps every trailing period at `withdrawal_limit`.
+    pub fn window_spent(&self, current_time: i64) -> Result<u64> {
+        let (_, previous_window_spent, window_spent) = self.withdrawal_window(current_time)?;
+
+        previous_window_spent
+            .checked_add(window_spent)
+            .ok_or(error!(ProtocolError::Overflow))
+    }
+
+    pub fn withdrawal_headroom(&self, current_time: i64) -> Result<WithdrawalHeadroom> {
+        let (window_start, _, _) = self.withdrawal_window(current_time)?;
+        let window_spent = self.window_spent(current_time)?;
+
+        Ok(WithdrawalHeadroom {
+            withdrawal_limit: self.withdrawal_limit,
+            withdrawal_period: self.withdrawal_period,
+            window_spent,
+            window_end: window_start.saturating_add(self.withdrawal_period),
+            available: self.withdrawal_limit.saturating_sub(window_spent),
+        })
+    }
+
+    pub fn record_withdrawal(&mut self, amount: u64, current_time: i64) -> Result<()> {
+        let (window_start, previous_window_spent, window_spent) =
+            self.withdrawal_window(current_time)?;
+        let window_spent = window_spent
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+        require!(
+            previous_window_spent
+                .checked_add(window_spent)
+                .ok_or(ProtocolError::Overflow)?
+                <= self.withdrawal_limit,
+            ProtocolError::WithdrawalLimitExceeded
+        );
+
+        self.window_start = window_start;
+        self.previous_window_spent = previous_window_spent;
+        self.window_spent = window_spent;
+
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreasuryIncome {
+    Deposit,
+    Penalty,
+    ProtocolFee,
+    BondProceeds,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum TreasuryAction {
+    Transfer { mint: Pubkey, destination: Pubkey, amount: u64 },
+    SetSigners { signers: Vec<Pubkey>, threshold: u8 },
+    ChangeThreshold { threshold: u8 },
+    SetRewardTopUp { pool: Pubkey, amount: u64, threshold: u64 },
+}
+
+impl TreasuryAction {
+    pub const MAX_LEN: usize = 1 + 4 + 32 * MAX_TREASURY_SIGNERS + 1;
+}
+
+#[account]
+#[derive(Debug)]
+pub struct TreasuryTransaction {
+    pub treasury: Pubkey,
+    pub proposer: Pubkey,
+    pub index: u64,
+    pub action: TreasuryAction,
+    pub approvals: [bool; MAX_TREASURY_SIGNERS],
+    pub owner_set_seqno: u32,
+    pub created_at: i64,
+    pub executed: bool,
+    pub bump: u8,
+}
+
+impl TreasuryTransaction {
+    pub const LEN: usize = 32 + 32 + 8 + TreasuryAction::MAX_LEN + MAX_TREASURY_SIGNERS + 4 + 8 + 1 + 1;
+
+    pub fn approve(&mut self, signer_index: usize) -> Result<u8> {
+        require!(!self.executed, ProtocolError::TransactionAlreadyExecuted);
+        require!(!self.approvals[signer_index], ProtocolError::AlreadyApproved);
+
+        self.approvals[signer_index] = true;
+
+        Ok(self.approval_count())
+    }
+
+    pub fn approval_count(&self) -> u8 {
+        self.approvals.iter().filter(|approved| **approved).count() as u8
+    }
+
+    pub fn is_stale(&self, treasury_state: &TreasuryState) -> bool {
+        self.owner_set_seqno != treasury_state.owner_set_seqno
+    }
+}
+
+#[account]
+#[derive(Debug)]
+pub struct WithdrawalState {
+    pub pool: Pubkey,
+    pub queue_head: u64,
+    pub queue_tail: u64,
+    pub total_queued: u64,
+    pub last_processed_time: i64,
+    pub bump: u8,
+}
+
+impl WithdrawalState {
+    pub const LEN: usize = 32 + 8 + 8 + 8 + 8 + 1;
+
+    pub fn enqueue(&mut self, amount: u64) -> Result<u64> {
+        let index = self.queue_tail;
+        self.queue_tail = self.queue_tail
+            .checked_add(1)
+            .ok_or(ProtocolError::Overflow)?;
+        self.total_queued = self.total_queued
+            .checked_add(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(index)
+    }
+
+    pub fn release(&mut self, amount: u64) -> Result<()> {
+        self.total_queued = self.total_queued
+            .checked_sub(amount)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    pub fn advance_head(&mut self) -> Result<()> {
+        self.queue_head = self.queue_head
+            .checked_add(1)
+            .ok_or(ProtocolError::Overflow)?;
+
+        Ok(())
+    }
+
+    pub fn pending_requests(&self) -> u64 {
+        self.queue_tail.saturating_sub(self.queue_head)
+    }
+}
+
+#[account]
+#[derive(Debug)]
+pub struct WithdrawalRequest {
+    pub queue: Pubkey,
+    pub owner: Pubkey,
+    pub position: u64,
+    pub index: u64,
+    pub amount: u64,
+    pub request_time: i64,
+    pub release_time: i64,
+    pub cancelled: bool,
+    pub bump: u8,
+}
+
+impl WithdrawalRequest {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1;
+
+    pub fn is_due(&self, current_time: i64) -> bool {
+        self.cancelled || current_time >= self.release_time
+    }
+}
+
+#[account]
+#[derive(Debug)]
+pub struct GovernanceState {
+    pub authority: Pubkey,
     pub voting_period: i64,
     pub required_quorum: u64,
     pub proposal_count: u64,
+    pub pool_count: u64,
+    pub voting_mint: Pubkey,
 }
 
 impl GovernanceState {
-    pub const LEN: usize = 8 + 8 + 8 + 8;
-}
\ No newline at end of file
+    pub const LEN: usize = 32 + 8 + 8 + 8 + 8 + 32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn pool() -> StakingState {
+        StakingState {
+            authority: Pubkey::default(),
+            stake_mint: Pubkey::default(),
+            current_apy: 10,
+            min_stake: 0,
+            max_stake: u64::MAX,
+            emergency_cooldown: 0,
+            paused: false,
+            total_staked: 0,
+            reward_per_token: 0,
+            last_reward_update: 0,
+            emergency_penalty: 0,
+            penalty_destination: PenaltyDestination::Treasury,
+            reward_reserve: 0,
+            total_rewards_distributed: 0,
+            reward_mode: RewardMode::Apy,
+            emission_rate: 0,
+            emission_step_interval: 0,
+            emission_step_percent: 0,
+            next_emission_step: 0,
+            bump: 0,
+            receipt_mint: Pubkey::default(),
+            liquid_staked: 0,
+            liquid_reward_per_token_paid: 0,
+            liquid_pending_rewards: 0,
+            lock_tiers: StakingState::default_lock_tiers(),
+            keeper_tip_bps: 0,
+            approved_depositors: [Pubkey::default(); MAX_APPROVED_DEPOSITORS],
+            liquidity_buffer_bps: 0,
+            liquidity_buffer_floor: 0,
+            total_effective_stake: 0,
+            unbonding_period: 0,
+            total_unbonding: 0,
+        }
+    }
+
+    fn position(amount: u64, multiplier_bps: u64) -> StakePosition {
+        StakePosition {
+            owner: Pubkey::default(),
+            pool: Pubkey::default(),
+            index: 0,
+            amount,
+            start_time: 0,
+            lock_end: 0,
+            lock_tier: 0,
+            lock_duration: 0,
+            multiplier_bps,
+            auto_compound: false,
+            reward_per_token_paid: 0,
+            pending_rewards: 0,
+            rewards_claimed: 0,
+            bump: 0,
+        }
+    }
+
+    fn emission_pool(rate: u64, step_percent: u64) -> StakingState {
+        let mut pool = pool();
+        pool.set_reward_mode(RewardMode::Emission, rate, DAY, step_percent, 0).unwrap();
+        pool
+    }
+
+    #[test]
+    fn emissions_match_stepwise_decay() {
+        let mut pool = emission_pool(1_000, 10);
+
+        let emitted = pool.emitted_until(3 * DAY + DAY / 2).unwrap();
+
+        let expected = (1_000 + 900 + 810) * DAY as u128 + 729 * (DAY / 2) as u128;
+        assert_eq!(emitted, expected);
+        assert_eq!(pool.emission_rate, 729);
+        assert_eq!(pool.next_emission_step, 4 * DAY);
+    }
+
+    #[test]
+    fn long_idle_emissions_stay_bounded() {
+        let mut pool = emission_pool(1_000_000, 1);
+
+        let emitted = pool.emitted_until(10 * 365 * DAY).unwrap();
+
+        // Never more than the undecayed geometric total of rate * interval / 1%.
+        assert!(emitted <= 100 * 1_000_000 * DAY as u128);
+        assert!(pool.emission_rate < 1_000_000);
+        assert!(pool.next_emission_step > 10 * 365 * DAY);
+    }
+
+    #[test]
+    fn emission_mode_skips_loyalty_bonus() {
+        let mut pool = emission_pool(1_000, 0);
+        assert!(!pool.loyalty_bonus_active());
+        pool.reward_mode = RewardMode::Apy;
+        assert!(pool.loyalty_bonus_active());
+    }
+
+    #[test]
+    fn pending_unbonding_blocks_a_second_request() {
+        let mut user_info = UserStakeInfo {
+            owner: Pubkey::default(),
+            pool: Pubkey::default(),
+            staked_amount: 0,
+            position_count: 0,
+            unbonding_amount: 0,
+            unbonding_release_time: 0,
+            last_emergency_withdraw: 0,
+        };
+
+        user_info.start_unbonding(100, DAY).unwrap();
+        assert_eq!(
+            user_info.start_unbonding(50, 2 * DAY).unwrap_err(),
+            error!(ProtocolError::UnbondingInProgress)
+        );
+        assert_eq!(user_info.unbonding_amount, 100);
+        assert_eq!(user_info.unbonding_release_time, DAY);
+    }
+
+    fn liquid_pool(liquid_staked: u64) -> StakingState {
+        let mut pool = pool();
+        pool.liquid_staked = liquid_staked;
+        pool.total_staked = liquid_staked;
+        pool
+    }
+
+    #[test]
+    fn first_liquid_deposit_mints_one_to_one() {
+        let pool = liquid_pool(0);
+        assert_eq!(pool.receipts_for_deposit(1_000, 0).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn receipt_conversions_round_toward_vault() {
+        // 3 underlying backing 2 receipts: 1.5 underlying per receipt.
+        let pool = liquid_pool(3);
+
+        // 2 underlying is worth 1.33 receipts, minted as 1.
+        assert_eq!(pool.receipts_for_deposit(2, 2).unwrap(), 1);
+        // 1 receipt is worth 1.5 underlying, redeemed as 1.
+        assert_eq!(pool.underlying_for_receipts(1, 2).unwrap(), 1);
+    }
+
+    #[test]
+    fn deposit_and_redeem_never_returns_more() {
+        let mut pool = liquid_pool(1_000_003);
+        let mut supply = 999_999;
+
+        for amount in [1, 7, 999, 123_457, 10_000_001] {
+            let receipts = pool.receipts_for_deposit(amount, supply).unwrap();
+            pool.add_liquid_stake(amount).unwrap();
+            supply += receipts;
+
+            let redeemed = pool.underlying_for_receipts(receipts, supply).unwrap();
+            assert!(redeemed <= amount);
+        }
+    }
+
+    #[test]
+    fn donations_do_not_move_exchange_rate() {
+        // The rate follows the tracked liquid stake rather than the vault balance, so
+        // tokens sent straight to the vault cannot inflate a lone early receipt.
+        let pool = liquid_pool(1);
+        assert_eq!(pool.underlying_for_receipts(1, 1).unwrap(), 1);
+        assert_eq!(pool.receipts_for_deposit(1_000_000, 1).unwrap(), 1_000_000);
+
+        let rate = pool.liquid_exchange_rate(0, 1, &treasury()).unwrap();
+        assert_eq!(rate.underlying_per_receipt, EXCHANGE_RATE_PRECISION as u64);
+    }
+
+    #[test]
+    fn exchange_rate_preview_deducts_protocol_fee() {
+        let mut pool = emission_pool(10, 10);
+        pool.add_liquid_stake(1_000).unwrap();
+        pool.reward_reserve = 1_000_000;
+        let mut treasury = treasury();
+        treasury.set_protocol_fee(1_000).unwrap();
+
+        // 1_000 emitted to liquid stake over 100 seconds, 10% of it kept as the fee.
+        let rate = pool.liquid_exchange_rate(100, 1_000, &treasury).unwrap();
+        assert_eq!(rate.total_underlying, 1_900);
+        assert_eq!(rate.underlying_per_receipt, 19 * EXCHANGE_RATE_PRECISION as u64 / 10);
+    }
+
+    #[test]
+    fn protocol_fee_rounds_down() {
+        let mut treasury = treasury();
+        assert_eq!(treasury.protocol_fee(1_000_000).unwrap(), 0);
+
+        treasury.set_protocol_fee(250).unwrap();
+        assert_eq!(treasury.protocol_fee(39).unwrap(), 0);
+        assert_eq!(treasury.protocol_fee(40).unwrap(), 1);
+        assert_eq!(treasury.protocol_fee(79).unwrap(), 1);
+        assert_eq!(treasury.protocol_fee(u64::MAX).unwrap(), u64::MAX / 40);
+
+        assert!(treasury.set_protocol_fee(MAX_PROTOCOL_FEE_BPS + 1).is_err());
+        assert_eq!(treasury.protocol_fee_bps, 250);
+    }
+
+    #[test]
+    fn boosted_positions_share_emissions_without_overpaying() {
+        let mut pool = pool();
+        pool.set_reward_mode(RewardMode::Emission, 1_000, 0, 0, 0).unwrap();
+
+        let plain = position(100, 10_000);
+        let boosted = position(100, 30_000);
+        for staked in [&plain, &boosted] {
+            pool.total_staked += staked.amount;
+            pool.reweight_stake(0, staked.effective_stake().unwrap()).unwrap();
+        }
+
+        let reward_per_token = pool.update_reward_index(100).unwrap();
+        let plain_rewards = plain.calculate_rewards(100, reward_per_token, false).unwrap();
+        let boosted_rewards = boosted.calculate_rewards(100, reward_per_token, false).unwrap();
+
+        assert_eq!(plain_rewards, 25_000);
+        assert_eq!(boosted_rewards, 75_000);
+        assert!(plain_rewards + boosted_rewards <= 100_000);
+    }
+
+    fn keeper(min_interval: i64) -> KeeperState {
+        KeeperState {
+            pool: Pubkey::default(),
+            tip_amount: 0,
+            min_interval,
+            reward_topup_amount: 0,
+            reward_topup_threshold: 0,
+            last_queue_process: 0,
+            last_reward_update: 0,
+            last_reward_topup: 0,
+            last_compound: 0,
+            total_tips_paid: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn keeper_interval_throttles_cranks_and_tips() {
+        let mut keeper = keeper(3_600);
+
+        for task in [KeeperTask::ProcessQueue, KeeperTask::Compound] {
+            assert!(keeper.record_run(task, 10_000).unwrap());
+            assert!(!keeper.record_run(task, 10_001).unwrap());
+            assert!(!keeper.record_run(task, 13_599).unwrap());
+            assert!(keeper.record_run(task, 13_600).unwrap());
+        }
+        for task in [KeeperTask::RewardIndex, KeeperTask::RewardTopUp] {
+            assert!(keeper.record_run(task, 10_000).unwrap());
+            assert!(keeper.record_run(task, 10_001).is_err());
+            assert!(keeper.record_run(task, 13_600).unwrap());
+        }
+    }
+
+    #[test]
+    fn exits_leave_pending_liabilities_and_buffer_covered() {
+        let mut pool = pool();
+        pool.total_staked = 1_000;
+        pool.liquidity_buffer_bps = 1_000;
+        pool.add_unbonding(300).unwrap();
+
+        // 300 unbonding, 200 queued and a 100 buffer leave 400 of a 1_000 vault free.
+        assert!(pool.exit_covered(1_000, 400, 200).unwrap());
+        assert!(!pool.exit_covered(1_000, 401, 200).unwrap());
+
+        pool.release_unbonding(300).unwrap();
+        assert!(pool.exit_covered(1_000, 700, 200).unwrap());
+        assert!(pool.exit_covered(u64::MAX, u64::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn withdrawal_queue_tracks_head_tail_and_total() {
+        let mut queue = WithdrawalState {
+            pool: Pubkey::default(),
+            queue_head: 0,
+            queue_tail: 0,
+            total_queued: 0,
+            last_processed_time: 0,
+                bump: 0,
+        };
+
+        assert_eq!(queue.enqueue(100).unwrap(), 0);
+        assert_eq!(queue.enqueue(250).unwrap(), 1);
+        assert_eq!(queue.enqueue(50).unwrap(), 2);
+        assert_eq!(queue.pending_requests(), 3);
+        assert_eq!(queue.total_queued, 400);
+
+        // A cancelled request releases its amount but keeps its slot until processed.
+        queue.release(250).unwrap();
+        assert_eq!(queue.total_queued, 150);
+        assert_eq!(queue.pending_requests(), 3);
+
+        queue.release(100).unwrap();
+        queue.advance_head().unwrap();
+        queue.advance_head().unwrap();
+        assert_eq!(queue.queue_head, 2);
+        assert_eq!(queue.pending_requests(), 1);
+        assert!(queue.release(51).is_err());
+
+        assert_eq!(queue.enqueue(10).unwrap(), 3);
+        assert_eq!(queue.pending_requests(), 2);
+    }
+
+    #[test]
+    fn cancelled_requests_are_due_immediately() {
+        let mut request = WithdrawalRequest {
+            queue: Pubkey::default(),
+            owner: Pubkey::default(),
+            position: 0,
+            index: 0,
+            amount: 100,
+            request_time: 0,
+            release_time: DAY,
+            cancelled: false,
+            bump: 0,
+        };
+
+        assert!(!request.is_due(DAY - 1));
+        assert!(request.is_due(DAY));
+        request.cancelled = true;
+        assert!(request.is_due(0));
+    }
+
+    fn bond_market(control_variable: u64, min_price: u64, capacity: u64) -> BondMarket {
+        BondMarket {
+            quote_mint: Pubkey::default(),
+            payout_mint: Pubkey::default(),
+            control_variable,
+            min_price,
+            capacity,
+            sold: 0,
+            total_debt: 0,
+            last_decay: 0,
+            vesting_term: DAY,
+            outstanding_payout: 0,
+            total_quote_received: 0,
+            adjustment: BondAdjustment::default(),
+            price_feed: Pubkey::default(),
+            max_price_age: 0,
+            max_confidence_bps: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn bond_price_floors_at_min_price() {
+        let market = bond_market(2_000_000, 150_000, 1_000);
+        assert_eq!(market.bond_price(1_000).unwrap(), 150_000);
+        assert_eq!(market.bond_price(0).unwrap(), 150_000);
+    }
+
+    #[test]
+    fn bond_price_follows_debt_ratio() {
+        let mut market = bond_market(2_000_000, 1, 1_000);
+        market.total_debt = 100;
+
+        // 100 debt over 1_000 supply is a 10% debt ratio.
+        assert_eq!(market.debt_ratio(1_000).unwrap(), DEBT_RATIO_PRECISION / 10);
+        assert_eq!(market.bond_price(1_000).unwrap(), 200_000);
+
+        market.total_debt = 200;
+        assert_eq!(market.bond_price(1_000).unwrap(), 400_000);
+    }
+
+    #[test]
+    fn debt_decays_to_zero_over_vesting_term() {
+        let mut market = bond_market(1, 1, 1_000);
+        market.total_debt = 1_000;
+
+        market.decay_debt(DAY / 2).unwrap();
+        assert_eq!(market.total_debt, 500);
+
+        market.decay_debt(DAY / 2 + DAY).unwrap();
+        assert_eq!(market.total_debt, 0);
+    }
+
+    #[test]
+    fn bonds_beyond_capacity_are_rejected() {
+        let mut market = bond_market(1, 1, 100);
+
+        market.record_bond(60, 60).unwrap();
+        assert!(market.record_bond(41, 41).is_err());
+        assert_eq!(market.sold, 60);
+
+        market.record_bond(40, 40).unwrap();
+        assert_eq!(market.sold, market.capacity);
+    }
+
+    fn bond(payout: u64, created_at: i64, maturity: i64) -> Bond {
+        Bond {
+            owner: Pubkey::default(),
+            market: Pubkey::default(),
+            index: 0,
+            deposit: 0,
+            payout,
+            price: 0,
+            created_at,
+            maturity,
+            vested_paid: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn nothing_vests_at_creation() {
+        let bond = bond(1_000, 10_000, 10_000 + DAY);
+        assert_eq!(bond.redeemable(10_000).unwrap(), 0);
+        assert_eq!(bond.redeemable(9_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn partial_redemptions_sum_to_payout() {
+        let mut bond = bond(1_001, 0, DAY);
+        let mut redeemed = 0;
+
+        for time in [DAY / 3, DAY / 2, DAY / 2, 2 * DAY / 3, DAY, 2 * DAY] {
+            let amount = bond.redeemable(time).unwrap();
+            assert!(bond.vested_paid + amount <= bond.vested(time).unwrap());
+            bond.record_redemption(amount).unwrap();
+            redeemed += amount;
+        }
+
+        assert_eq!(redeemed, bond.payout);
+        assert!(bond.fully_redeemed());
+        assert_eq!(bond.redeemable(3 * DAY).unwrap(), 0);
+    }
+
+    #[test]
+    fn full_payout_vests_at_maturity() {
+        let bond = bond(1_000, 0, DAY);
+        assert_eq!(bond.redeemable(DAY / 2).unwrap(), 500);
+        assert_eq!(bond.redeemable(DAY).unwrap(), bond.payout);
+    }
+
+    fn price_feed(price: i64, conf: u64, expo: i32, publish_time: i64) -> PriceFeed {
+        PriceFeed {
+            authority: Pubkey::default(),
+            mint: Pubkey::default(),
+            price,
+            conf,
+            expo,
+            publish_time,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn stale_prices_are_rejected() {
+        let feed = price_feed(100_000, 0, -5, 10_000);
+
+        assert_eq!(feed.validate(10_060, 60, 100).unwrap(), 100_000);
+        assert_eq!(
+            feed.validate(10_061, 60, 100).unwrap_err(),
+            error!(ProtocolError::StalePrice)
+        );
+    }
+
+    #[test]
+    fn low_confidence_prices_are_rejected() {
+        // 1_000 over 100_000 is a 1% confidence interval.
+        let feed = price_feed(100_000, 1_000, -5, 10_000);
+
+        assert_eq!(feed.validate(10_000, 60, 100).unwrap(), 100_000);
+        assert_eq!(
+            feed.validate(10_000, 60, 99).unwrap_err(),
+            error!(ProtocolError::PriceConfidenceTooLow)
+        );
+        assert!(price_feed(0, 0, -5, 10_000).validate(10_000, 60, 100).is_err());
+        assert!(price_feed(-1, 0, -5, 10_000).validate(10_000, 60, 100).is_err());
+    }
+
+    #[test]
+    fn value_of_scales_by_exponent_and_decimals() {
+        // 2.5 payout tokens per quote token, 6 quote decimals and 9 payout decimals.
+        let feed = price_feed(250, 0, -2, 0);
+        assert_eq!(feed.value_of(1_000_000, 250, 6, 9).unwrap(), 2_500_000_000);
+        assert_eq!(feed.value_of(1, 250, 9, 6).unwrap(), 0);
+
+        let feed = price_feed(3, 0, 2, 0);
+        assert_eq!(feed.value_of(1_000_000, 3, 6, 6).unwrap(), 300_000_000);
+        assert_eq!(feed.value_of(1_000_000, 3, 6, 4).unwrap(), 3_000_000);
+        assert!(feed.value_of(u64::MAX, 3, 0, 0).is_err());
+    }
+
+    #[test]
+    fn unpriced_values_follow_mint_decimals() {
+        assert_eq!(rescale(1_000_000, 0, 6, 9).unwrap(), 1_000_000_000);
+        assert_eq!(rescale(1_000_000_000, 0, 9, 6).unwrap(), 1_000_000);
+        assert_eq!(rescale(999, 0, 9, 6).unwrap(), 0);
+    }
+
+    fn ledger(withdrawal_limit: u64, withdrawal_period: i64) -> TreasuryLedger {
+        TreasuryLedger {
+            mint: Pubkey::default(),
+            vault: Pubkey::default(),
+            total_balance: 0,
+            deposit_income: 0,
+            penalty_income: 0,
+            fee_income: 0,
+            bond_income: 0,
+            bond_liabilities: 0,
+            withdrawal_limit,
+            withdrawal_period,
+            window_start: 0,
+            window_spent: 0,
+            previous_window_spent: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn withdrawal_limit_holds_over_any_trailing_period() {
+        let mut ledger = ledger(1_000, DAY);
+        let mut withdrawals = Vec::new();
+
+        // Drain all headroom every hour, including right before and after each rollover.
+        for time in (0..5 * DAY).step_by(3_600).flat_map(|time| [time, time + 3_599]) {
+            let available = ledger.withdrawal_headroom(time).unwrap().available;
+            if available > 0 {
+                ledger.record_withdrawal(available, time).unwrap();
+                withdrawals.push((time, available));
+            }
+            assert!(ledger.record_withdrawal(1, time).is_err());
+        }
+
+        for &(end, _) in &withdrawals {
+            let spent: u64 = withdrawals
+                .iter()
+                .filter(|(time, _)| *time > end - DAY && *time <= end)
+                .map(|(_, amount)| amount)
+                .sum();
+            assert!(spent <= ledger.withdrawal_limit);
+        }
+        assert!(withdrawals.iter().map(|(_, amount)| amount).sum::<u64>() >= 2_000);
+    }
+
+    #[test]
+    fn withdrawal_window_releases_after_two_rollovers() {
+        let mut ledger = ledger(1_000, DAY);
+
+        ledger.record_withdrawal(1_000, DAY - 1).unwrap();
+        let headroom = ledger.withdrawal_headroom(DAY).unwrap();
+        assert_eq!(headroom.available, 0);
+        assert_eq!(headroom.window_end, 2 * DAY);
+
+        assert_eq!(ledger.withdrawal_headroom(2 * DAY).unwrap().available, 1_000);
+        ledger.record_withdrawal(400, 3 * DAY + 5).unwrap();
+        assert_eq!(ledger.window_start, 3 * DAY);
+        assert_eq!(ledger.previous_window_spent, 0);
+        assert_eq!(ledger.window_spent(4 * DAY).unwrap(), 400);
+    }
+
+    #[test]
+    fn ledger_tracks_income_spend_and_sync() {
+        let mut ledger = ledger(0, DAY);
+
+        ledger.record_income(TreasuryIncome::Deposit, 500).unwrap();
+        ledger.record_income(TreasuryIncome::Penalty, 200).unwrap();
+        ledger.record_income(TreasuryIncome::ProtocolFee, 30).unwrap();
+        ledger.record_income(TreasuryIncome::BondProceeds, 70).unwrap();
+        assert_eq!(
+            (ledger.deposit_income, ledger.penalty_income, ledger.fee_income, ledger.bond_income),
+            (500, 200, 30, 70)
+        );
+        assert_eq!(ledger.total_balance, 800);
+
+        // Spends come out of the balance but never the income counters.
+        ledger.record_spend(300);
+        assert_eq!(ledger.total_balance, 500);
+        assert_eq!(ledger.deposit_income, 500);
+        ledger.record_spend(600);
+        assert_eq!(ledger.total_balance, 0);
+
+        assert_eq!(ledger.sync_balance(250), 250);
+        assert_eq!(ledger.sync_balance(100), -150);
+        assert_eq!(ledger.total_balance, 100);
+
+        ledger.total_balance = u64::MAX;
+        assert!(ledger.record_income(TreasuryIncome::Deposit, 1).is_err());
+    }
+
+    #[test]
+    fn ledgers_are_limited_per_mint() {
+        let mut base = ledger(1_000, DAY);
+        let mut other = ledger(0, DAY);
+
+        // A new ledger allows nothing until governance sets its limit.
+        assert!(other.record_withdrawal(1, 0).is_err());
+        base.record_withdrawal(1_000, 0).unwrap();
+
+        other.set_withdrawal_limit(50, DAY).unwrap();
+        other.record_withdrawal(50, 0).unwrap();
+        assert!(other.record_withdrawal(1, 0).is_err());
+        assert!(base.record_withdrawal(1, 0).is_err());
+        assert!(other.set_withdrawal_limit(50, 0).is_err());
+    }
+
+    #[test]
+    fn bond_liabilities_are_reserved_across_markets() {
+        let mut ledger = ledger(0, DAY);
+
+        // Two markets paying out of the same 1_000 vault.
+        ledger.reserve_bond_payout(1_000, 600).unwrap();
+        assert!(ledger.reserve_bond_payout(1_000, 401).is_err());
+        ledger.reserve_bond_payout(1_000, 400).unwrap();
+
+        assert!(ledger.require_unreserved(1_000, 1).is_err());
+        ledger.require_unreserved(1_250, 250).unwrap();
+
+        ledger.release_bond_payout(600).unwrap();
+        ledger.require_unreserved(1_000, 600).unwrap();
+        assert!(ledger.release_bond_payout(401).is_err());
+    }
+
+    fn treasury() -> TreasuryState {
+        TreasuryState {
+            authority: Pubkey::default(),
+            epoch_outflow_cap: 0,
+            epoch_duration: 0,
+            epoch_start: 0,
+            epoch_outflow: 0,
+            required_signatures: 1,
+            signers: [Pubkey::default(); MAX_TREASURY_SIGNERS],
+            signer_count: 0,
+            owner_set_seqno: 0,
+            transaction_count: 0,
+            protocol_fee_bps: 0,
+        }
+    }
+
+    fn signers(count: u8) -> Vec<Pubkey> {
+        (1..=count).map(|seed| Pubkey::new_from_array([seed; 32])).collect()
+    }
+
+    fn transaction(treasury: &TreasuryState) -> TreasuryTransaction {
+        TreasuryTransaction {
+            treasury: Pubkey::default(),
+            proposer: Pubkey::default(),
+            index: 0,
+            action: TreasuryAction::ChangeThreshold { threshold: 1 },
+            approvals: [false; MAX_TREASURY_SIGNERS],
+            owner_set_seqno: treasury.owner_set_seqno,
+            created_at: 0,
+            executed: false,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn invalid_signer_sets_are_rejected() {
+        let mut treasury = treasury();
+        let owners = signers(3);
+
+        assert!(treasury.set_signers(&[], 1).is_err());
+        assert!(treasury.set_signers(&owners, 0).is_err());
+        assert!(treasury.set_signers(&owners, 4).is_err());
+        assert!(treasury.set_signers(&signers(MAX_TREASURY_SIGNERS as u8 + 1), 1).is_err());
+        assert!(treasury.set_signers(&[owners[0], owners[1], owners[0]], 2).is_err());
+        assert!(treasury.set_signers(&[owners[0], Pubkey::default()], 1).is_err());
+        assert_eq!(treasury.owner_set_seqno, 0);
+
+        treasury.set_signers(&owners, 2).unwrap();
+        assert_eq!(treasury.active_signers(), &owners[..]);
+        assert_eq!(treasury.required_signatures, 2);
+        assert_eq!(treasury.signer_index(&owners[2]).unwrap(), 2);
+        assert!(treasury.signer_index(&Pubkey::default()).is_err());
+    }
+
+    #[test]
+    fn each_signer_approves_once() {
+        let mut treasury = treasury();
+        treasury.set_signers(&signers(3), 2).unwrap();
+        let mut transaction = transaction(&treasury);
+
+        assert_eq!(transaction.approve(0).unwrap(), 1);
+        assert_eq!(
+            transaction.approve(0).unwrap_err(),
+            error!(ProtocolError::AlreadyApproved)
+        );
+        assert_eq!(transaction.approve(2).unwrap(), 2);
+        assert_eq!(transaction.approval_count(), 2);
+
+        transaction.executed = true;
+        assert!(transaction.approve(1).is_err());
+    }
+
+    #[test]
+    fn signer_changes_leave_pending_transactions_stale() {
+        let mut treasury = treasury();
+        let owners = signers(3);
+        treasury.set_signers(&owners, 2).unwrap();
+        let transaction = transaction(&treasury);
+        assert!(!transaction.is_stale(&treasury));
+
+        // Even re-installing the same owners invalidates approvals gathered before.
+        treasury.set_signers(&owners, 2).unwrap();
+        assert!(transaction.is_stale(&treasury));
+    }
+
+    #[test]
+    fn reward_topup_stays_within_withdrawal_limit() {
+        let mut keeper = keeper(0);
+
+        assert!(keeper.set_reward_topup(1_001, 500, 1_000).is_err());
+        keeper.set_reward_topup(1_000, 500, 1_000).unwrap();
+        assert_eq!(keeper.reward_topup_amount, 1_000);
+        assert_eq!(keeper.reward_topup_threshold, 500);
+    }
+}
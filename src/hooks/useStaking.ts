@@ -45,7 +45,7 @@ export function useStaking() {
       }
 
       try {
-        const stakingState = await client.getStakingState(publicKey);
+        const stakingState = await client.getStakingState();
         const userStakeInfo = await client.getUserStakeInfo(publicKey);
         
         setStakedBalance(userStakeInfo?.stakedAmount?.toNumber() || 0);
@@ -84,7 +84,7 @@ export function useStaking() {
       const wallet = (window as any).solana;
       const client = new DeathProtocolClient(connection, wallet);
       
-      const transaction = await client.stake(amount, publicKey);
+      const transaction = await client.openPosition(amount, publicKey);
       
       const signature = await signAndSendTransaction(transaction, connection);
       
@@ -1,123 +1,84 @@
 import { Program, AnchorProvider, Idl, BN } from '@project-serum/anchor';
 import { Connection, PublicKey, SystemProgram, Transaction } from '@solana/web3.js';
-import { getAssociatedTokenAddress, TOKEN_PROGRAM_ID, createAssociatedTokenAccountInstruction } from '@solana/spl-token';
+import { getAssociatedTokenAddress, TOKEN_PROGRAM_ID } from '@solana/spl-token';
 import { DEATH_MINT, DEATH_STAKING_PROGRAM_ID } from '../utils/constants';
 import IDL from '../idl/death_protocol.json';
 
 export class DeathProtocolClient {
   private program: Program;
   private connection: Connection;
+  private stakeMint: PublicKey;
 
-  constructor(connection: Connection, wallet: any) {
+  constructor(connection: Connection, wallet: any, stakeMint: PublicKey = DEATH_MINT) {
     const provider = new AnchorProvider(connection, wallet, { commitment: 'confirmed' });
     this.program = new Program(IDL as Idl, DEATH_STAKING_PROGRAM_ID, provider);
     this.connection = connection;
+    this.stakeMint = stakeMint;
   }
 
-  async getStakingState(authority: PublicKey) {
-    try {
-      const [stakingStateAddress] = PublicKey.findProgramAddressSync(
-        [Buffer.from('staking'), authority.toBuffer()],
-        this.program.programId
-      );
-      
-      return await this.program.account.stakingState.fetch(stakingStateAddress);
-    } catch (error) {
-      // Initialize if account doesn't exist
-      await this.initializeStakingState(authority);
-      const [stakingStateAddress] = PublicKey.findProgramAddressSync(
-        [Buffer.from('staking'), authority.toBuffer()],
-        this.program.programId
-      );
-      return await this.program.account.stakingState.fetch(stakingStateAddress);
-    }
+  // Each stake mint has its own pool, keyed by the mint.
+  stakingStateAddress(): PublicKey {
+    return this.findAddress([Buffer.from('staking_state'), this.stakeMint.toBuffer()]);
   }
 
-  async initializeStakingState(authority: PublicKey) {
-    const [stakingStateAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from('staking'), authority.toBuffer()],
-      this.program.programId
-    );
+  userStakeInfoAddress(user: PublicKey): PublicKey {
+    return this.findAddress([
+      Buffer.from('user_stake'),
+      this.stakingStateAddress().toBuffer(),
+      user.toBuffer(),
+    ]);
+  }
 
-    return await this.program.methods
-      .initialize()
-      .accounts({
-        authority,
-        stakingState: stakingStateAddress,
-        systemProgram: SystemProgram.programId,
-      })
-      .rpc();
+  positionAddress(user: PublicKey, index: number): PublicKey {
+    return this.findAddress([
+      Buffer.from('stake_position'),
+      this.stakingStateAddress().toBuffer(),
+      user.toBuffer(),
+      new BN(index).toArrayLike(Buffer, 'le', 8),
+    ]);
   }
 
-  async stake(amount: number, user: PublicKey): Promise<Transaction> {
-    const [stakingStateAddress] = await PublicKey.findProgramAddress(
-      [Buffer.from('staking'), user.toBuffer()],
-      this.program.programId
-    );
+  async getStakingState() {
+    return await this.program.account.stakingState.fetch(this.stakingStateAddress());
+  }
 
-    const [userStakeInfoAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from('user_stake'), user.toBuffer()],
-      this.program.programId
+  async getUserStakeInfo(userPubkey: PublicKey) {
+    return await this.program.account.userStakeInfo.fetchNullable(
+      this.userStakeInfoAddress(userPubkey)
     );
+  }
 
-    // Get or create token accounts
-    const userTokenAccount = await getAssociatedTokenAddress(DEATH_MINT, user, false);
-    const protocolTokenAccount = await getAssociatedTokenAddress(
-      DEATH_MINT,
-      stakingStateAddress,
-      true // Allow PDA account
-    );
+  async getPosition(userPubkey: PublicKey, index: number) {
+    return await this.program.account.stakePosition.fetch(this.positionAddress(userPubkey, index));
+  }
 
-    // Check if protocol token account exists
-    const protocolAccount = await this.connection.getAccountInfo(protocolTokenAccount);
-    
-    let transaction = await this.program.methods
-      .stake(new BN(amount))
+  async openPosition(amount: number, user: PublicKey, lockTier = 0): Promise<Transaction> {
+    const stakingState = this.stakingStateAddress();
+    const userStakeInfo = await this.getUserStakeInfo(user);
+    const positionIndex = userStakeInfo ? (userStakeInfo.positionCount as BN).toNumber() : 0;
+
+    return await this.program.methods
+      .openPosition(new BN(amount), lockTier)
       .accounts({
         user,
-        stakingState: stakingStateAddress,
-        userStakeInfo: userStakeInfoAddress,
-        userTokenAccount,
-        protocolTokenAccount,
+        stakingState,
+        userStakeInfo: this.userStakeInfoAddress(user),
+        position: this.positionAddress(user, positionIndex),
+        userTokenAccount: await getAssociatedTokenAddress(this.stakeMint, user),
+        principalVault: this.findAddress([Buffer.from('principal_vault'), stakingState.toBuffer()]),
+        stakeMint: this.stakeMint,
         tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
       .transaction();
-
-    // Add create protocol token account instruction if needed
-    if (!protocolAccount) {
-      transaction.add(
-        createAssociatedTokenAccountInstruction(
-          user,
-          protocolTokenAccount,
-          stakingStateAddress,
-          DEATH_MINT
-        )
-      );
-    }
-
-    return transaction;
-  }
-
-  async getUserStakeInfo(userPubkey: PublicKey) {
-    const [userStakeInfoAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from('user_stake'), userPubkey.toBuffer()],
-      this.program.programId
-    );
-    
-    return await this.program.account.userStakeInfo.fetch(userStakeInfoAddress);
   }
 
-  async unstake(amount: number, user: PublicKey): Promise<Transaction> {
-    const [stakingStateAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from('staking'), user.toBuffer()],
-      this.program.programId
-    );
-
-    const [userStakeInfoAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from('user_stake'), user.toBuffer()],
-      this.program.programId
-    );
+  // Pays out instantly when the vault covers the exit, otherwise joins the withdrawal queue.
+  async unstake(amount: number, user: PublicKey, positionIndex = 0): Promise<Transaction> {
+    const stakingState = this.stakingStateAddress();
+    const withdrawalState = this.findAddress([Buffer.from('withdrawal_state'), stakingState.toBuffer()]);
+    const queue = await this.program.account.withdrawalState.fetchNullable(withdrawalState);
+    const queueTail = queue ? (queue.queueTail as BN) : new BN(0);
 
     const amountInLamports = new BN(amount * 1e9);
 
@@ -125,10 +86,19 @@ export class DeathProtocolClient {
       .unstake(amountInLamports)
       .accounts({
         user,
-        stakingState: stakingStateAddress,
-        userStakeInfo: userStakeInfoAddress,
-        userTokenAccount: await getAssociatedTokenAddress(DEATH_MINT, user),
-        protocolTokenAccount: await getAssociatedTokenAddress(DEATH_MINT, stakingStateAddress, true),
+        stakingState,
+        treasuryState: this.findAddress([Buffer.from('treasury_state')]),
+        withdrawalState,
+        userStakeInfo: this.userStakeInfoAddress(user),
+        position: this.positionAddress(user, positionIndex),
+        withdrawalRequest: this.findAddress([
+          Buffer.from('withdrawal_request'),
+          withdrawalState.toBuffer(),
+          queueTail.toArrayLike(Buffer, 'le', 8),
+        ]),
+        userTokenAccount: await getAssociatedTokenAddress(this.stakeMint, user),
+        principalVault: this.findAddress([Buffer.from('principal_vault'), stakingState.toBuffer()]),
+        stakeMint: this.stakeMint,
         tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
@@ -142,27 +112,22 @@ export class DeathProtocolClient {
     return transaction;
   }
 
-  async claimRewards(user: PublicKey): Promise<Transaction> {
-    const [stakingStateAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from('staking'), user.toBuffer()],
-      this.program.programId
-    );
-
-    const [userStakeInfoAddress] = PublicKey.findProgramAddressSync(
-      [Buffer.from('user_stake'), user.toBuffer()],
-      this.program.programId
-    );
+  async claimRewards(user: PublicKey, positionIndex = 0): Promise<Transaction> {
+    const stakingState = this.stakingStateAddress();
 
     const transaction = await this.program.methods
       .claimRewards()
       .accounts({
         user,
-        stakingState: stakingStateAddress,
-        userStakeInfo: userStakeInfoAddress,
-        userTokenAccount: await getAssociatedTokenAddress(DEATH_MINT, user),
-        protocolTokenAccount: await getAssociatedTokenAddress(DEATH_MINT, stakingStateAddress, true),
+        stakingState,
+        position: this.positionAddress(user, positionIndex),
+        userTokenAccount: await getAssociatedTokenAddress(this.stakeMint, user),
+        rewardVault: this.findAddress([Buffer.from('reward_vault'), stakingState.toBuffer()]),
+        treasuryState: this.findAddress([Buffer.from('treasury_state')]),
+        treasuryLedger: this.findAddress([Buffer.from('treasury_ledger'), this.stakeMint.toBuffer()]),
+        treasuryTokenAccount: this.findAddress([Buffer.from('treasury_vault'), this.stakeMint.toBuffer()]),
+        stakeMint: this.stakeMint,
         tokenProgram: TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
       })
       .transaction();
 
@@ -173,4 +138,9 @@ export class DeathProtocolClient {
 
     return transaction;
   }
-}
\ No newline at end of file
+
+  private findAddress(seeds: Buffer[]): PublicKey {
+    const [address] = PublicKey.findProgramAddressSync(seeds, this.program.programId);
+    return address;
+  }
+}
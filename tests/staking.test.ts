@@ -1,18 +1,21 @@
 import * as anchor from '@project-serum/anchor';
 import { Program } from '@project-serum/anchor';
 import { PublicKey } from '@solana/web3.js';
+import { mintTo } from '@solana/spl-token';
 import { expect } from 'chai';
 import { setupTest } from './setup';
 import { DeathProtocol } from '../target/types/death_protocol';
 
+let program: Program<DeathProtocol>;
+
 describe('Death Protocol Staking Tests', () => {
-  let program: Program<DeathProtocol>;
   let connection, authority, user, mint, authorityATA, userATA;
 
   before(async () => {
     // Setup test environment
     const setup = await setupTest();
     ({ connection, authority, user, mint, authorityATA, userATA } = setup);
+    await mintTo(connection, authority, mint, userATA.address, authority, 1_000_000);
 
     // Initialize program
     const provider = anchor.AnchorProvider.env();
@@ -22,45 +25,94 @@ describe('Death Protocol Staking Tests', () => {
 
   it('Initializes the protocol', async () => {
     const config = {
-      baseApy: 1000, // 10%
-      minStake: 100,
-      maxStake: 1000000,
-      emergencyCooldown: 7 * 24 * 60 * 60,
-      withdrawalLimit: 10000,
-      votingPeriod: 3 * 24 * 60 * 60,
+      treasurySigners: [authority.publicKey],
+      requiredSignatures: 1,
+      withdrawalLimit: new anchor.BN(10000),
+      withdrawalPeriod: new anchor.BN(24 * 60 * 60),
+      epochOutflowCap: new anchor.BN(0), // No cap
+      epochDuration: new anchor.BN(24 * 60 * 60),
+      protocolFeeBps: new anchor.BN(500), // 5%
+      votingPeriod: new anchor.BN(3 * 24 * 60 * 60),
     };
 
     await program.methods
       .initialize(config)
       .accounts({
         authority: authority.publicKey,
-        stakingState: await getStakingStateAddress(),
-        treasuryState: await getTreasuryStateAddress(),
-        governanceState: await getGovernanceStateAddress(),
+        treasuryState: getTreasuryStateAddress(),
+        treasuryMint: mint,
+        treasuryLedger: getTreasuryLedgerAddress(mint),
+        treasuryVault: getTreasuryVaultAddress(mint),
+        governanceState: getGovernanceStateAddress(),
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
       })
       .signers([authority])
       .rpc();
 
-    const stakingState = await program.account.stakingState.fetch(
-      await getStakingStateAddress()
-    );
+    const treasuryLedger = await program.account.treasuryLedger.fetch(getTreasuryLedgerAddress(mint));
+
+    expect(treasuryLedger.withdrawalLimit.toNumber()).to.equal(config.withdrawalLimit.toNumber());
+    expect(treasuryLedger.mint.toBase58()).to.equal(mint.toBase58());
+  });
+
+  it('Creates a pool for the stake mint', async () => {
+    const config = {
+      baseApy: new anchor.BN(10), // 10%
+      minStake: new anchor.BN(100),
+      maxStake: new anchor.BN(1000000),
+      emergencyCooldown: new anchor.BN(7 * 24 * 60 * 60),
+      emergencyPenalty: new anchor.BN(10),
+      penaltyDestination: { treasury: {} },
+      unbondingPeriod: new anchor.BN(7 * 24 * 60 * 60),
+      keeperTipBps: new anchor.BN(0),
+      liquidityBufferBps: new anchor.BN(0),
+      liquidityBufferFloor: new anchor.BN(0),
+      keeper: { tipAmount: new anchor.BN(0), minInterval: new anchor.BN(0) },
+    };
+    const stakingState = getStakingStateAddress(mint);
+
+    await program.methods
+      .createPool(config)
+      .accounts({
+        authority: authority.publicKey,
+        governanceState: getGovernanceStateAddress(),
+        stakeMint: mint,
+        stakingState,
+        principalVault: getPoolAddress('principal_vault', stakingState),
+        rewardVault: getPoolAddress('reward_vault', stakingState),
+        keeperState: getPoolAddress('keeper_state', stakingState),
+        keeperBudget: getPoolAddress('keeper_budget', stakingState),
+        treasuryState: getTreasuryStateAddress(),
+        treasuryLedger: getTreasuryLedgerAddress(mint),
+        treasuryVault: getTreasuryVaultAddress(mint),
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const pool = await program.account.stakingState.fetch(stakingState);
 
-    expect(stakingState.currentApy.toNumber()).to.equal(config.baseApy);
-    expect(stakingState.minStake.toNumber()).to.equal(config.minStake);
+    expect(pool.stakeMint.toBase58()).to.equal(mint.toBase58());
+    expect(pool.currentApy.toNumber()).to.equal(config.baseApy.toNumber());
+    expect(pool.minStake.toNumber()).to.equal(config.minStake.toNumber());
   });
 
-  it('Stakes tokens', async () => {
+  it('Opens a stake position', async () => {
     const amount = new anchor.BN(1000);
+    const stakingState = getStakingStateAddress(mint);
 
     await program.methods
-      .stake(amount)
+      .openPosition(amount, 0)
       .accounts({
         user: user.publicKey,
-        stakingState: await getStakingStateAddress(),
-        userStakeInfo: await getUserStakeInfoAddress(user.publicKey),
+        stakingState,
+        userStakeInfo: getUserStakeInfoAddress(stakingState, user.publicKey),
+        position: getPositionAddress(stakingState, user.publicKey, 0),
         userTokenAccount: userATA.address,
-        protocolTokenAccount: await getProtocolTokenAccount(),
+        principalVault: getPoolAddress('principal_vault', stakingState),
+        stakeMint: mint,
         tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
       })
@@ -68,52 +120,59 @@ describe('Death Protocol Staking Tests', () => {
       .rpc();
 
     const userStakeInfo = await program.account.userStakeInfo.fetch(
-      await getUserStakeInfoAddress(user.publicKey)
+      getUserStakeInfoAddress(stakingState, user.publicKey)
+    );
+    const position = await program.account.stakePosition.fetch(
+      getPositionAddress(stakingState, user.publicKey, 0)
     );
 
     expect(userStakeInfo.stakedAmount.toNumber()).to.equal(amount.toNumber());
+    expect(userStakeInfo.positionCount.toNumber()).to.equal(1);
+    expect(position.amount.toNumber()).to.equal(amount.toNumber());
   });
 
   // Add more test cases for other functionalities
 });
 
 // Helper functions to get PDA addresses
-async function getStakingStateAddress(): Promise<PublicKey> {
-  const [address] = await PublicKey.findProgramAddress(
-    [Buffer.from('staking_state')],
-    program.programId
-  );
+function findAddress(seeds: Buffer[]): PublicKey {
+  const [address] = PublicKey.findProgramAddressSync(seeds, program.programId);
   return address;
 }
 
-async function getTreasuryStateAddress(): Promise<PublicKey> {
-  const [address] = await PublicKey.findProgramAddress(
-    [Buffer.from('treasury_state')],
-    program.programId
-  );
-  return address;
+function getStakingStateAddress(stakeMint: PublicKey): PublicKey {
+  return findAddress([Buffer.from('staking_state'), stakeMint.toBuffer()]);
 }
 
-async function getGovernanceStateAddress(): Promise<PublicKey> {
-  const [address] = await PublicKey.findProgramAddress(
-    [Buffer.from('governance_state')],
-    program.programId
-  );
-  return address;
+function getPoolAddress(seed: string, stakingState: PublicKey): PublicKey {
+  return findAddress([Buffer.from(seed), stakingState.toBuffer()]);
 }
 
-async function getUserStakeInfoAddress(user: PublicKey): Promise<PublicKey> {
-  const [address] = await PublicKey.findProgramAddress(
-    [Buffer.from('user_stake_info'), user.toBuffer()],
-    program.programId
-  );
-  return address;
+function getTreasuryStateAddress(): PublicKey {
+  return findAddress([Buffer.from('treasury_state')]);
 }
 
-async function getProtocolTokenAccount(): Promise<PublicKey> {
-  const [address] = await PublicKey.findProgramAddress(
-    [Buffer.from('protocol_token_account')],
-    program.programId
-  );
-  return address;
-}
\ No newline at end of file
+function getTreasuryLedgerAddress(mint: PublicKey): PublicKey {
+  return findAddress([Buffer.from('treasury_ledger'), mint.toBuffer()]);
+}
+
+function getTreasuryVaultAddress(mint: PublicKey): PublicKey {
+  return findAddress([Buffer.from('treasury_vault'), mint.toBuffer()]);
+}
+
+function getGovernanceStateAddress(): PublicKey {
+  return findAddress([Buffer.from('governance_state')]);
+}
+
+function getUserStakeInfoAddress(stakingState: PublicKey, user: PublicKey): PublicKey {
+  return findAddress([Buffer.from('user_stake'), stakingState.toBuffer(), user.toBuffer()]);
+}
+
+function getPositionAddress(stakingState: PublicKey, user: PublicKey, index: number): PublicKey {
+  return findAddress([
+    Buffer.from('stake_position'),
+    stakingState.toBuffer(),
+    user.toBuffer(),
+    new anchor.BN(index).toArrayLike(Buffer, 'le', 8),
+  ]);
+}